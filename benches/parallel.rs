@@ -0,0 +1,119 @@
+//! Throughput of [`Parallel`] on a batch mixing large and small images.
+//!
+//! Run with `cargo bench --bench parallel`.
+//! Results come back in order through a reorder buffer, so threads that finish early move on to the next image
+//! instead of waiting for the ones before them. Ordered throughput should stay close to [`Ordering::AsCompleted`],
+//! and well above the previous behaviour, where each thread spun until the images before its own were sent.
+use crossbeam::channel;
+use image::{ImageFormat::Jpeg, Rgb, RgbImage};
+use jippigy::{Ordering, Parallel, Single};
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 5;
+const DEVICE: u8 = 4;
+
+fn create_jpeg_image(width: u32, height: u32) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    // noise keeps the encoder busy, a blank image compresses too fast to measure anything.
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        let v = x.wrapping_mul(31) ^ y.wrapping_mul(17);
+        Rgb([v as u8, (v >> 3) as u8, (v >> 5) as u8])
+    });
+    img.write_to(&mut Cursor::new(&mut jpeg), Jpeg).unwrap();
+    jpeg
+}
+/// One large image followed by several small ones, repeated.
+fn mixed_batch() -> Vec<Vec<u8>> {
+    let large = create_jpeg_image(4000, 3000);
+    let small = create_jpeg_image(200, 150);
+    let mut batch = Vec::new();
+    for _ in 0..4 {
+        batch.push(large.clone());
+        for _ in 0..15 {
+            batch.push(small.clone());
+        }
+    }
+    batch
+}
+fn run(batch: &[Vec<u8>], ordering: Ordering) -> Duration {
+    let start = Instant::now();
+    for result in Parallel::from_vec(batch.to_vec())
+        .with_device(DEVICE)
+        .with_ordering(ordering)
+        .build()
+        .into_iter()
+    {
+        result.unwrap();
+    }
+    start.elapsed()
+}
+/// The previous behaviour of [`Ordering::InOrder`]: a thread holding a compressed image spins on a shared counter
+/// until it is the image's turn to be sent, and only then takes the next image.
+fn run_spin_wait(batch: &[Vec<u8>]) -> Duration {
+    let start = Instant::now();
+    let queue = Arc::new(Mutex::new(
+        batch.iter().cloned().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let counter = Arc::new(Mutex::new(0usize));
+    let (tx, rx) = channel::unbounded();
+    let handles = (0..DEVICE)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let counter = Arc::clone(&counter);
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                let Some((index, bytes)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+                let result = Single::from_bytes(bytes).build().compress();
+                loop {
+                    let mut counter = counter.lock().unwrap();
+                    if *counter == index {
+                        *counter += 1;
+                        tx.send(result).unwrap();
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+    for result in rx {
+        result.unwrap();
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+fn report(label: &str, batch: &[Vec<u8>], best: Duration) {
+    println!(
+        "{:<12} {:>8.1} ms {:>8.1} images/s",
+        label,
+        best.as_secs_f64() * 1000.0,
+        batch.len() as f64 / best.as_secs_f64()
+    );
+}
+fn main() {
+    let batch = mixed_batch();
+    println!(
+        "{} images, {DEVICE} threads, best of {ROUNDS} rounds",
+        batch.len()
+    );
+    for ordering in [Ordering::InOrder, Ordering::AsCompleted] {
+        let best = (0..ROUNDS)
+            .map(|_| run(&batch, ordering))
+            .min()
+            .unwrap_or_default();
+        report(&ordering.to_string(), &batch, best);
+    }
+    let best = (0..ROUNDS)
+        .map(|_| run_spin_wait(&batch))
+        .min()
+        .unwrap_or_default();
+    report("spin-wait", &batch, best);
+}
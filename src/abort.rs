@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stops an in-flight [`Parallel`](crate::Parallel) compression task, possibly from another thread.
+/// Obtained with [`ParallelIntoIterator::abort_handle`](crate::ParallelIntoIterator::abort_handle) and the equivalent methods of the other iterators.
+///
+/// Once aborted, threads stop taking new images and the iterator returns no more items.
+/// turbojpeg cannot be interrupted, so images that are already being compressed are finished first.
+#[derive(Debug, Clone, Default)]
+pub struct AbortHandle(Arc<AtomicBool>);
+impl AbortHandle {
+    /// Aborts the compression task. Aborting it more than once has no further effect.
+    pub fn abort(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    /// Whether the compression task has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
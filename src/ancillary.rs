@@ -0,0 +1,165 @@
+use crate::jpeg;
+use img_parts::{
+    jpeg::{Jpeg, JpegSegment},
+    Bytes,
+};
+use std::fmt::Display;
+
+/// Start of the XMP packet.
+const XMP: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Start of extended XMP, for XMP packets that do not fit in a single segment.
+const XMP_EXTENSION: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+/// Position of the color transform in an Adobe APP14 segment, after `Adobe`, its version and flags.
+const ADOBE_TRANSFORM: usize = 11;
+
+/// Ancillary segments of the original image (besides EXIF data and ICC profiles) that are carried over into the compressed image.
+///
+/// All of them are preserved by default, see [`Ancillary::ALL`].
+/// EXIF data is chosen with [`MetadataPolicy`](crate::MetadataPolicy) instead, and ICC profiles are always preserved.
+/// XMP, IPTC and other APP segments can hold the same data as EXIF data (e.g. GPS coordinates or serial numbers),
+/// so they are dropped whenever the [`MetadataPolicy`](crate::MetadataPolicy) strips anything, even if listed here.
+/// JFIF (APP0) and Multi-Picture Format (APP2) segments describe the original file itself, so they are never carried over.
+/// # Example
+/// ```
+/// use jippigy::{Ancillary, Single};
+/// let bytes: Vec<u8> = Vec::new();
+/// // keep captions and copyright, drop everything else.
+/// let _build = Single::from_bytes(bytes)
+///     .with_ancillary([Ancillary::Xmp, Ancillary::Iptc])
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Ancillary {
+    /// XMP metadata (APP1 with Adobe's XMP namespace), extended XMP included.
+    Xmp,
+    /// Photoshop image resources, including IPTC captions, keywords and copyright (APP13).
+    Iptc,
+    /// Adobe color transform information (APP14).
+    ///
+    /// Lossless recompressions always carry it over, since the color transform of the original image is kept.
+    /// Otherwise its color transform is rewritten to match the compressed image.
+    Adobe,
+    /// Comments (COM).
+    Comment,
+    /// Any other APPn segment, by its number n (from 1 to 15). For example, `App(1)` is an APP1 segment that is neither EXIF data nor XMP.
+    App(u8),
+}
+impl Ancillary {
+    /// Every ancillary segment, which is the default.
+    pub const ALL: &'static [Ancillary] = &[
+        Ancillary::Xmp,
+        Ancillary::Iptc,
+        Ancillary::Adobe,
+        Ancillary::Comment,
+        Ancillary::App(1),
+        Ancillary::App(2),
+        Ancillary::App(3),
+        Ancillary::App(4),
+        Ancillary::App(5),
+        Ancillary::App(6),
+        Ancillary::App(7),
+        Ancillary::App(8),
+        Ancillary::App(9),
+        Ancillary::App(10),
+        Ancillary::App(11),
+        Ancillary::App(12),
+        Ancillary::App(13),
+        Ancillary::App(14),
+        Ancillary::App(15),
+    ];
+    /// Whether the segment can hold metadata also found in EXIF data, such as GPS coordinates or serial numbers.
+    fn carries_metadata(&self) -> bool {
+        matches!(self, Ancillary::Xmp | Ancillary::Iptc | Ancillary::App(_))
+    }
+    /// Classifies a segment. Returns `None` for segments that are not ancillary,
+    /// or are handled elsewhere (EXIF data and ICC profiles) or never carried over (JFIF and MPF).
+    fn classify(marker: u8, contents: &[u8]) -> Option<Self> {
+        match marker {
+            jpeg::APP0 => None,
+            jpeg::APP1 if contents.starts_with(b"Exif\0\0") => None,
+            jpeg::APP1 if contents.starts_with(XMP) || contents.starts_with(XMP_EXTENSION) => {
+                Some(Ancillary::Xmp)
+            }
+            jpeg::APP2
+                if contents.starts_with(b"ICC_PROFILE\0") || contents.starts_with(b"MPF\0") =>
+            {
+                None
+            }
+            jpeg::APP13 if contents.starts_with(b"Photoshop 3.0\0") => Some(Ancillary::Iptc),
+            jpeg::APP14 if contents.starts_with(b"Adobe") => Some(Ancillary::Adobe),
+            jpeg::COM => Some(Ancillary::Comment),
+            jpeg::APP1..=jpeg::APP15 => Some(Ancillary::App(marker - jpeg::APP0)),
+            _ => None,
+        }
+    }
+}
+impl Display for Ancillary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ancillary::Xmp => write!(f, "XMP"),
+            Ancillary::Iptc => write!(f, "IPTC"),
+            Ancillary::Adobe => write!(f, "Adobe"),
+            Ancillary::Comment => write!(f, "comments"),
+            Ancillary::App(n) => write!(f, "APP{n}"),
+        }
+    }
+}
+/// Displays a list of ancillary segments.
+pub(crate) struct Kept<'a>(pub(crate) &'a [Ancillary]);
+impl Display for Kept<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == Ancillary::ALL {
+            return write!(f, "all");
+        }
+        if self.0.is_empty() {
+            return write!(f, "none");
+        }
+        let names: Vec<String> = self.0.iter().map(|kept| kept.to_string()).collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+/// Replaces the ancillary segments of `compressed` with the `kept` ancillary segments of `original`, in their original order.
+/// They are inserted after the APP segments of `compressed`.
+///
+/// `strip_metadata` drops the segments that can hold metadata also found in EXIF data, whether they are `kept` or not.
+/// `reencoded` tells whether `compressed` has been re-encoded (as opposed to losslessly recompressed).
+pub(crate) fn preserve(
+    original: &Jpeg,
+    compressed: &mut Jpeg,
+    kept: &[Ancillary],
+    strip_metadata: bool,
+    reencoded: bool,
+) {
+    let components = compressed
+        .segments()
+        .iter()
+        .find(|segment| jpeg::is_frame(segment.marker()))
+        .and_then(|frame| frame.contents().get(5).copied());
+    let carried = original.segments().iter().filter_map(|segment| {
+        let class = Ancillary::classify(segment.marker(), segment.contents())?;
+        match class {
+            Ancillary::Adobe if !reencoded => Some((segment.marker(), segment.contents().clone())),
+            _ if !kept.contains(&class) => None,
+            _ if strip_metadata && class.carries_metadata() => None,
+            Ancillary::Adobe => {
+                let mut contents = segment.contents().to_vec();
+                if let Some(transform) = contents.get_mut(ADOBE_TRANSFORM) {
+                    // compressed images are YCbCr, or grayscale which has no transform.
+                    *transform = u8::from(components == Some(3));
+                }
+                Some((segment.marker(), Bytes::from(contents)))
+            }
+            _ => Some((segment.marker(), segment.contents().clone())),
+        }
+    });
+    let carried: Vec<_> = carried
+        .map(|(marker, contents)| JpegSegment::new_with_contents(marker, contents))
+        .collect();
+    let segments = compressed.segments_mut();
+    segments.retain(|segment| Ancillary::classify(segment.marker(), segment.contents()).is_none());
+    let after_app = segments
+        .iter()
+        .rposition(|segment| matches!(segment.marker(), jpeg::APP0..=jpeg::APP15))
+        .map_or(0, |last| last + 1);
+    segments.splice(after_app..after_app, carried);
+}
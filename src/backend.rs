@@ -0,0 +1,43 @@
+#[cfg(feature = "rayon")]
+use crate::by_address::ByAddress;
+use std::fmt::Display;
+#[cfg(feature = "rayon")]
+use std::sync::Arc;
+
+/// A rayon thread pool provided by the caller.
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Pool(ByAddress<rayon::ThreadPool>);
+#[cfg(feature = "rayon")]
+impl Pool {
+    pub(crate) fn new(pool: Arc<rayon::ThreadPool>) -> Self {
+        Self(ByAddress(pool))
+    }
+    pub(crate) fn current_num_threads(&self) -> usize {
+        self.0.current_num_threads()
+    }
+    pub(crate) fn spawn(&self, op: impl FnOnce() + Send + 'static) {
+        self.0.spawn(op)
+    }
+}
+/// Where the images of a [`Parallel`](crate::Parallel) are compressed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub(crate) enum Backend {
+    /// Threads spawned for each compression task.
+    #[default]
+    Threads,
+    /// The threads of a rayon pool provided by the caller.
+    #[cfg(feature = "rayon")]
+    Rayon(Pool),
+}
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Threads => write!(f, "threads"),
+            #[cfg(feature = "rayon")]
+            Backend::Rayon(pool) => {
+                write!(f, "rayon pool ({} threads)", pool.current_num_threads())
+            }
+        }
+    }
+}
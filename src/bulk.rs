@@ -1,17 +1,30 @@
-use crate::{error, Compress, DEVICE, QUALITY};
+use crate::{
+    backend::Backend,
+    error,
+    input::{Input, Lazy, Queue},
+    progress::{Callback, Tracker},
+    resize, ssim, AbortHandle, Ancillary, Compress, CompressionOutput, Filter, Lossless, Margin,
+    MetadataPolicy, Mode, Ordering, Progress, Resize, Settings, Subsampling, BUFFER, DEVICE,
+};
 use crossbeam::channel;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Display;
-use std::sync::{Arc, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 /// Custom configuration for building a [`Parallel`].
 /// This struct is not meant to be used directly.
-/// Use [`Parallel::from_vec`] instead.
+/// Use [`Parallel::from_vec`], [`Parallel::from_iter`] or [`Parallel::from_channel`] instead.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
 pub struct ParallelBuilder {
-    vec: VecDeque<(usize, Vec<u8>)>,
-    quality: u8,
+    input: Input,
+    settings: Settings,
     device_num: u8,
+    buffer: usize,
+    ordering: Ordering,
+    backend: Backend,
+    progress: Option<Callback>,
 }
 impl ParallelBuilder {
     /// Builds a new [`Parallel`] with default or specified configuration.
@@ -25,12 +38,15 @@ impl ParallelBuilder {
     /// # }
     /// ```
     pub fn build(self) -> Parallel {
-        let (tx, rx) = channel::unbounded();
+        let (tx, rx) = channel::bounded(self.buffer);
         Parallel {
             to_thread: ToThread {
-                vec: self.vec,
+                input: self.input,
                 device_num: self.device_num,
-                quality: self.quality,
+                settings: self.settings,
+                ordering: self.ordering,
+                backend: self.backend,
+                progress: self.progress,
             },
             transmitter: tx,
             receiver: rx,
@@ -42,9 +58,206 @@ impl ParallelBuilder {
     /// **This method is optional**.
     pub fn with_quality(self, quality: u8) -> ParallelBuilder {
         ParallelBuilder {
-            vec: self.vec,
-            quality,
-            device_num: self.device_num,
+            settings: Settings {
+                quality,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies the chroma subsampling of compressed images.
+    /// Defaults to [`Subsampling::S420`] (2x2 chroma subsampling).
+    /// Use [`Subsampling::Auto`] to keep the subsampling of each original image.
+    ///
+    /// **This method is optional**.
+    pub fn with_subsampling(self, subsampling: Subsampling) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                subsampling,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether compressed images are encoded as progressive JPEGs, which are displayed in increasing detail as they load.
+    /// Defaults to `false` (baseline JPEGs).
+    /// Lossless recompressions use [`Lossless::Progressive`] instead.
+    ///
+    /// **This method is optional**.
+    pub fn with_progressive(self, progressive: bool) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                progressive,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Downscales each image to fit within `width` by `height` before compressing, keeping its aspect ratio.
+    /// Images that already fit are not resized, and images are never upscaled.
+    /// Lossless recompressions are not resized.
+    ///
+    /// **This method is optional**.
+    pub fn with_max_dimensions(self, width: u32, height: u32) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                resize: Resize::MaxDimensions(width, height),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Downscales each image by `factor` before compressing, e.g. `0.5` halves its width and height.
+    /// The factor is clamped between 0.0 (exclusive) and 1.0 (inclusive), so images are never upscaled.
+    /// Lossless recompressions are not resized.
+    ///
+    /// **This method is optional**.
+    pub fn with_scale(self, factor: f64) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                resize: Resize::Scale(resize::Scale::from(factor)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies the resampling filter used when resizing with [`with_max_dimensions`](ParallelBuilder::with_max_dimensions) or [`with_scale`](ParallelBuilder::with_scale).
+    /// Defaults to [`Filter::Lanczos3`].
+    ///
+    /// Images are first shrunk while decoding with turbojpeg's DCT scaling (by a factor of n/8),
+    /// the filter only resizes what remains.
+    ///
+    /// **This method is optional**.
+    pub fn with_filter(self, filter: Filter) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                filter,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether each image is rotated (or flipped) upright according to its EXIF Orientation tag before compressing.
+    /// The Orientation tag of the preserved EXIF data is then reset to 1 (upright), so that viewers do not rotate it again.
+    /// Defaults to `false` (pixels and EXIF data are kept as they are).
+    /// Lossless recompressions are not rotated.
+    ///
+    /// **This method is optional**.
+    pub fn with_auto_orient(self, auto_orient: bool) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                auto_orient,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies which EXIF data of each original image is preserved, e.g. to strip GPS coordinates and serial numbers.
+    /// Defaults to [`MetadataPolicy::KeepAll`]. See [`MetadataPolicy`] for the available policies.
+    ///
+    /// **This method is optional**.
+    pub fn with_metadata(self, metadata: MetadataPolicy) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                metadata,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies which ancillary segments of each original image (XMP, IPTC, comments, etc.) are preserved, besides EXIF data and ICC profiles.
+    /// Defaults to [`Ancillary::ALL`]. Pass an empty list to preserve none of them.
+    ///
+    /// **This method is optional**.
+    pub fn with_ancillary(self, ancillary: impl Into<Arc<[Ancillary]>>) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                ancillary: ancillary.into(),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether an original image is kept as is when compressing it would make it worse or larger:
+    /// - if its quality, estimated from its quantization tables, is lower than the quality given by [`with_quality`](ParallelBuilder::with_quality),
+    ///   re-encoding it would only add artifacts. When compressing towards a target, the search is capped at its quality instead.
+    /// - if the compressed image is not smaller than the original one, unless a margin is given with [`with_size_guard`](ParallelBuilder::with_size_guard).
+    ///
+    /// Defaults to `false`. Use [`CompressionOutput::outcome`](crate::CompressionOutput::outcome) to know whether the original image has been kept.
+    ///
+    /// **This method is optional**.
+    pub fn with_skip_if_lower_quality(self, skip: bool) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                skip_lower_quality: skip,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Keeps an original image as is unless its compressed image is smaller by at least `margin`, a fraction of the original size
+    /// from 0.0 to 1.0. For example, `0.05` requires compressing to save at least 5% of the original size,
+    /// and `0.0` only requires the compressed image to be smaller.
+    ///
+    /// Without it, compressed images are returned even if they are larger than the original ones, as often happens with already optimized images.
+    /// Use [`CompressionOutput::outcome`](crate::CompressionOutput::outcome) to know whether an original image has been kept.
+    ///
+    /// **This method is optional**.
+    pub fn with_size_guard(self, margin: f64) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                size_guard: Some(Margin::from(margin)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Compresses each image to a file size of at most `bytes`, EXIF data included, instead of using a fixed quality.
+    /// The highest quality that fits is searched for, up to the quality given by [`with_quality`](ParallelBuilder::with_quality).
+    /// Use [`Parallel::into_output_iter`] to know which quality was chosen for each image.
+    ///
+    /// Images that do not fit even with the lowest quality result in [`Error::TargetError`](crate::Error::TargetError).
+    ///
+    /// **This method is optional**.
+    pub fn with_target_size(self, bytes: usize) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                mode: Mode::TargetSize(bytes),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Compresses each image as far as possible while keeping it at least `similarity` similar to its original,
+    /// instead of using a fixed quality. Similarity is measured with [SSIM](https://en.wikipedia.org/wiki/Structural_similarity)
+    /// on the decoded images, from 0.0 (unrelated) to 1.0 (identical), and is clamped to this range.
+    /// The lowest quality that is similar enough is searched for, up to the quality given by [`with_quality`](ParallelBuilder::with_quality).
+    /// Use [`Parallel::into_output_iter`] to know which quality was chosen for each image and the similarity it reached.
+    ///
+    /// Images that are not similar enough even with the highest quality result in [`Error::TargetError`](crate::Error::TargetError).
+    ///
+    /// **This method is optional**.
+    pub fn with_min_similarity(self, similarity: f64) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                mode: Mode::MinSimilarity(ssim::Score::from(similarity)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Recompresses each image losslessly instead of re-encoding: only the entropy coding is rewritten
+    /// and images are never decoded, so no quality is lost. See [`Lossless`] for the available methods.
+    /// Quality and subsampling are not used.
+    ///
+    /// **This method is optional**.
+    pub fn with_lossless(self, lossless: Lossless) -> ParallelBuilder {
+        ParallelBuilder {
+            settings: Settings {
+                mode: Mode::Lossless(lossless),
+                ..self.settings
+            },
+            ..self
         }
     }
     /// Specifies the number of threads to be used.
@@ -56,99 +269,243 @@ impl ParallelBuilder {
     /// However, spawning many threads has diminishing returns and not to mention it can be costly.
     /// Experiment as you please, but if you don't know what number to put in simply don't use this method as it is optional.
     pub fn with_device(self, device_num: u8) -> ParallelBuilder {
+        ParallelBuilder { device_num, ..self }
+    }
+    /// Specifies how many compressed images can wait to be consumed by the iterator.
+    /// Defaults to 8.
+    ///
+    /// Once the buffer is full, threads stop compressing until the iterator catches up,
+    /// so that a slow consumer (e.g. writing to a slow disk) does not pile up compressed images in memory.
+    /// With a buffer of 0, each compressed image is handed over to the iterator directly.
+    ///
+    /// **This method is optional**.
+    pub fn with_buffer(self, buffer: usize) -> ParallelBuilder {
+        ParallelBuilder { buffer, ..self }
+    }
+    /// Specifies the order in which compressed images are returned.
+    /// Defaults to [`Ordering::InOrder`], the same order they were passed in.
+    ///
+    /// With [`Ordering::AsCompleted`], each image is returned as soon as it is compressed,
+    /// so that an image that takes long to compress does not hold back the others.
+    /// Use [`Parallel::into_indexed_iter`] to know which image each result belongs to.
+    ///
+    /// **This method is optional**.
+    pub fn with_ordering(self, ordering: Ordering) -> ParallelBuilder {
+        ParallelBuilder { ordering, ..self }
+    }
+    /// Calls `progress` each time an image is done, whether it has been compressed or not, e.g. to update a progress bar.
+    /// See [`Progress`] for what is reported.
+    ///
+    /// It is called from the threads compressing the images, one call at a time and in the order images are done,
+    /// so it should return quickly to not hold them back. A panic in `progress` is caught and only skips that report.
+    ///
+    /// **This method is optional**.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     for result in Parallel::from_vec(vector_of_bytes)
+    ///         .with_progress(|progress| {
+    ///             let total = progress.total().unwrap_or_default();
+    ///             println!("{}/{total}, {:.0} bytes/s", progress.completed(), progress.throughput());
+    ///         })
+    ///         .build()
+    ///         .into_iter()
+    ///     {
+    ///         let _bytes: Vec<u8> = result?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_progress(
+        self,
+        progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> ParallelBuilder {
         ParallelBuilder {
-            vec: self.vec,
-            quality: self.quality,
-            device_num,
+            progress: Some(Callback::new(progress)),
+            ..self
         }
     }
+    /// Compresses images on the threads of a rayon pool instead of spawning new threads,
+    /// e.g. to share the pool an application already uses and avoid oversubscribing CPUs.
+    /// As many images are compressed at once as the pool has threads, [`with_device`](ParallelBuilder::with_device) is not used.
+    ///
+    /// Images are still returned through the same iterators, in the order given by [`with_ordering`](ParallelBuilder::with_ordering).
+    /// While the buffer given by [`with_buffer`](ParallelBuilder::with_buffer) is full, the pool's threads wait for the iterator to catch up,
+    /// so the iterator should not be consumed from within the same pool.
+    /// Dropping the iterator stops the pool's threads from taking new images, without waiting for them.
+    ///
+    /// Requires the `rayon` feature.
+    ///
+    /// **This method is optional**.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// use std::sync::Arc;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(4).build()?);
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     for result in Parallel::from_vec(vector_of_bytes)
+    ///         .with_pool(Arc::clone(&pool))
+    ///         .build()
+    ///         .into_iter()
+    ///     {
+    ///         let _bytes: Vec<u8> = result?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn with_pool(self, pool: std::sync::Arc<rayon::ThreadPool>) -> ParallelBuilder {
+        ParallelBuilder {
+            backend: Backend::Rayon(crate::backend::Pool::new(pool)),
+            ..self
+        }
+    }
+    /// Replaces the images to compress, keeping every other setting.
+    pub(crate) fn with_input(self, input: Input) -> ParallelBuilder {
+        ParallelBuilder { input, ..self }
+    }
 }
 impl Display for ParallelBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "len: {}\nquality: {}\ndevice_num: {}",
-            self.vec.len(),
-            self.quality,
-            self.device_num
+            "len: {}\n{}\ndevice_num: {}\nbuffer: {}\nordering: {}\nbackend: {}",
+            self.input, self.settings, self.device_num, self.buffer, self.ordering, self.backend
         )
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
 pub struct ToThread {
-    vec: VecDeque<(usize, Vec<u8>)>,
+    input: Input,
     device_num: u8,
-    quality: u8,
+    settings: Settings,
+    ordering: Ordering,
+    backend: Backend,
+    progress: Option<Callback>,
 }
 impl ToThread {
     /// Compress images in parallel.
+    /// Each result is sent along with its index as soon as it is ready, the iterator puts them back in order if needed.
     fn send_to_threads(
         self,
-        tx: channel::Sender<Result<Vec<u8>, error::Error>>,
+        tx: channel::Sender<(usize, Result<CompressionOutput, error::Error>)>,
+        abort: &AbortHandle,
+        window: Option<Arc<Window>>,
     ) -> Vec<thread::JoinHandle<()>> {
-        let mut handles = Vec::with_capacity(usize::from(self.device_num));
-        let counter = Arc::new(Mutex::new(0usize));
-        let to_steal_from = Arc::new(Mutex::new(self.vec));
-        for _ in 0..self.device_num {
+        let tracker = self
+            .progress
+            .map(|callback| Arc::new(Tracker::new(callback, self.input.len())));
+        let to_steal_from = Arc::new(Mutex::new(self.input.into_queue()));
+        let settings = self.settings;
+        let work = move || {
             let local_stealer = Arc::clone(&to_steal_from);
-            let local_counter = Arc::clone(&counter);
             let local_transmitter = tx.clone();
-            let handle = thread::spawn(move || {
-                let mut payload = Vec::with_capacity(1);
-                loop {
-                    {
-                        let Some(mut stealer_guard) = local_stealer.lock().ok() else {
-                            continue;
-                        };
-                        if let Some(bytes) = stealer_guard.pop_front() {
-                            payload.push(bytes);
-                        } else {
-                            break;
-                        }
-                        // lock is no longer needed past this point
-                    }
-                    if let Some(content) = payload.pop() {
-                        let compress_result = Compress::new(content.1, self.quality).compress();
-                        loop {
-                            {
-                                let Some(mut counter_guard) = local_counter.lock().ok() else {
-                                    continue;
-                                };
-                                // if the counter matches the index...
-                                if !(*counter_guard == content.0) {
-                                    continue;
-                                } else {
-                                    // ...increment this counter...
-                                    *counter_guard = *counter_guard + 1;
-                                }
-                                // ...and send it down the channel.
-                                match local_transmitter.send(compress_result) {
-                                    Err(e) => {
-                                        eprintln!("{e:#?}");
-                                    }
-                                    Ok(_) => {}
-                                }
-                            }
-                            break;
-                        }
-                    }
-                    payload.clear();
+            let local_abort = abort.clone();
+            let local_tracker = tracker.clone();
+            let local_window = window.clone();
+            let local_settings = settings.clone();
+            move || {
+                Self::work(
+                    local_stealer,
+                    local_settings,
+                    local_transmitter,
+                    local_abort,
+                    local_tracker,
+                    local_window,
+                )
+            }
+        };
+        match self.backend {
+            Backend::Threads => (0..self.device_num)
+                .map(|_| thread::spawn(work()))
+                .collect(),
+            #[cfg(feature = "rayon")]
+            Backend::Rayon(pool) => {
+                for _ in 0..pool.current_num_threads() {
+                    pool.spawn(work());
                 }
-            });
-            handles.push(handle);
+                Vec::new()
+            }
+        }
+    }
+    /// Returns how many threads compress the images.
+    fn threads(&self) -> usize {
+        match &self.backend {
+            Backend::Threads => usize::from(self.device_num),
+            #[cfg(feature = "rayon")]
+            Backend::Rayon(pool) => pool.current_num_threads(),
+        }
+    }
+    /// Takes images from the queue and compresses them one at a time, until the queue is empty or the task is aborted.
+    fn work(
+        stealer: Arc<Mutex<Queue>>,
+        settings: Settings,
+        transmitter: channel::Sender<(usize, Result<CompressionOutput, error::Error>)>,
+        abort: AbortHandle,
+        tracker: Option<Arc<Tracker>>,
+        window: Option<Arc<Window>>,
+    ) {
+        #[cfg(feature = "tracing")]
+        let _worker = tracing::debug_span!("worker").entered();
+        let mut payload = Vec::with_capacity(1);
+        loop {
+            if abort.is_aborted() {
+                break;
+            }
+            // with `Ordering::InOrder`, waits for the iterator to catch up before taking an image too far ahead of it.
+            if let Some(window) = &window {
+                if !window.claim(&abort) {
+                    break;
+                }
+            }
+            {
+                // waits for the next image, which may have to be read first with lazy input.
+                #[cfg(feature = "tracing")]
+                let _wait = tracing::debug_span!("queue_wait").entered();
+                // panics while pulling from lazy input are caught by the queue, so the lock is never poisoned.
+                let Some(mut stealer_guard) = stealer.lock().ok() else {
+                    break;
+                };
+                if let Some(bytes) = stealer_guard.next() {
+                    payload.push(bytes);
+                } else {
+                    break;
+                }
+                // lock is no longer needed past this point
+            }
+            if let Some((index, content)) = payload.pop() {
+                let size = content.as_ref().map_or(0, Vec::len);
+                let compress_result = content.and_then(|bytes| {
+                    // a panic only loses the image that caused it, the thread moves on to the next one.
+                    let compress = Compress::new(bytes, settings.clone()).with_index(index);
+                    panic::catch_unwind(AssertUnwindSafe(|| compress.compress()))
+                        .unwrap_or(Err(error::Error::WorkerPanicked { index }))
+                });
+                if let Some(tracker) = &tracker {
+                    let compressed_size = compress_result
+                        .as_ref()
+                        .map_or(0, |output| output.bytes().len());
+                    tracker.record(size, compressed_size);
+                }
+                // sending only fails once the iterator has been dropped, when results are no longer wanted.
+                if abort.is_aborted() || transmitter.send((index, compress_result)).is_err() {
+                    break;
+                }
+            }
+            payload.clear();
         }
-        handles
     }
 }
 impl Display for ToThread {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "len: {}\nquality: {}\ndevice_num: {}",
-            self.vec.len(),
-            self.quality,
-            self.device_num
+            "len: {}\n{}\ndevice_num: {}\nordering: {}\nbackend: {}",
+            self.input, self.settings, self.device_num, self.ordering, self.backend
         )
     }
 }
@@ -156,14 +513,17 @@ impl Display for ToThread {
 #[derive(Debug, Clone)]
 pub struct Parallel {
     to_thread: ToThread,
-    transmitter: channel::Sender<Result<Vec<u8>, error::Error>>,
-    receiver: channel::Receiver<Result<Vec<u8>, error::Error>>,
+    transmitter: channel::Sender<(usize, Result<CompressionOutput, error::Error>)>,
+    receiver: channel::Receiver<(usize, Result<CompressionOutput, error::Error>)>,
 }
 impl Parallel {
     /// Creates a parallelized compression task from a vector of bytes. Returns a [`ParallelBuilder`].
     /// This method initializes the compression task with the following defaults:
     /// - Default final quality is 95% (95% of the original quality).
+    /// - Default chroma subsampling is 4:2:0 (2x2).
+    /// - Compressed images are baseline (not progressive) JPEGs.
     /// - Default number of threads spawned is 2.
+    /// - Default number of compressed images waiting to be consumed is 8.
     /// # Example
     /// ```
     /// use jippigy::Parallel;
@@ -187,18 +547,192 @@ impl Parallel {
     /// ```
     pub fn from_vec(vec: Vec<Vec<u8>>) -> ParallelBuilder {
         ParallelBuilder {
-            vec: vec
-                .into_iter()
-                .enumerate()
-                .map(|content| content)
-                .collect::<VecDeque<(usize, Vec<u8>)>>(),
-            quality: QUALITY,
+            input: Input::Vec(
+                vec.into_iter()
+                    .enumerate()
+                    .collect::<VecDeque<(usize, Vec<u8>)>>(),
+            ),
+            settings: Settings::default(),
+            device_num: DEVICE,
+            buffer: BUFFER,
+            ordering: Ordering::default(),
+            backend: Backend::default(),
+            progress: None,
+        }
+    }
+    /// Creates a parallelized compression task from an iterator of bytes, with the same defaults as [`from_vec`](Parallel::from_vec).
+    /// Returns a [`ParallelBuilder`].
+    ///
+    /// Images are pulled from the iterator as threads become available, instead of being held in memory up front.
+    /// Only the images being compressed, and those waiting for the images before them, are held in memory at once.
+    /// Items are still returned in the same order they were pulled in.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// use std::path::PathBuf;
+    /// # const TEST_DIR: &str = "./tests/images/";
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let paths: Vec<PathBuf> = std::fs::read_dir(TEST_DIR)?
+    ///         .flatten()
+    ///         .map(|entry| entry.path())
+    ///         .filter(|path| path.is_file())
+    ///         .collect();
+    ///     // each file is only read once a thread is ready to compress it.
+    ///     let files = paths.clone().into_iter().map(|path| {
+    ///         std::fs::read(&path).unwrap_or_else(|e| {
+    ///             eprintln!("{}: {e}", path.display());
+    ///             // no bytes fail to compress in place of the file, so results stay aligned with `paths`.
+    ///             Vec::new()
+    ///         })
+    ///     });
+    ///     for (result, path) in Parallel::from_iter(files).build().into_iter().zip(paths) {
+    ///         match result {
+    ///             Ok(bytes) => println!("{}: {} bytes", path.display(), bytes.len()),
+    ///             Err(e) => eprintln!("{}: {e}", path.display()),
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    // not `FromIterator::from_iter`, which can neither return a builder nor require a `Send` iterator.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<I>(iter: I) -> ParallelBuilder
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        ParallelBuilder {
+            input: Input::Lazy(Lazy::new(iter.into_iter())),
+            settings: Settings::default(),
             device_num: DEVICE,
+            buffer: BUFFER,
+            ordering: Ordering::default(),
+            backend: Backend::default(),
+            progress: None,
         }
     }
-    fn compress(self) -> Vec<JoinHandle<()>> {
-        let handles = self.to_thread.send_to_threads(self.transmitter);
-        handles
+    /// Creates a parallelized compression task fed through a channel, with the same defaults as [`from_vec`](Parallel::from_vec).
+    /// Returns the sending half of the channel along with a [`ParallelBuilder`].
+    ///
+    /// At most `capacity` images wait in the channel: sending blocks until a thread is ready to compress the next image.
+    /// Compression ends once every sender has been dropped.
+    /// Dropping the iterator waits for threads waiting on the channel, so senders should not be kept idle past it.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// use image::{RgbImage, ImageFormat::Jpeg};
+    /// use std::io::Cursor;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let (sender, builder) = Parallel::from_channel(4);
+    ///     let producer = std::thread::spawn(move || {
+    ///         for _ in 0..10 {
+    ///             let mut bytes = Vec::new();
+    ///             let img = RgbImage::new(100, 100);
+    ///             let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg);
+    ///             if sender.send(bytes).is_err() {
+    ///                 break;
+    ///             }
+    ///         }
+    ///     });
+    ///     for result in builder.build().into_iter() {
+    ///         let _bytes: Vec<u8> = result?;
+    ///     }
+    ///     let _join = producer.join();
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_channel(capacity: usize) -> (mpsc::SyncSender<Vec<u8>>, ParallelBuilder) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        (sender, Parallel::from_iter(receiver))
+    }
+    /// Starts the compression like [`into_iter`](Parallel::into_iter()) does, but the iterator returns
+    /// [`CompressionOutput`]s, which carry details on how each image was compressed.
+    /// Items are returned in the same order they were passed in, unless [`Ordering::AsCompleted`] is used.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     let outputs = Parallel::from_vec(vector_of_bytes)
+    ///         .with_target_size(200 * 1024)
+    ///         .build()
+    ///         .into_output_iter();
+    ///     for result in outputs {
+    ///         let output = result?;
+    ///         println!("compressed with quality {:?}", output.quality());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn into_output_iter(self) -> ParallelOutputIterator {
+        ParallelOutputIterator::new(self.into_indexed_iter())
+    }
+    /// Starts the compression like [`into_output_iter`](Parallel::into_output_iter) does, but the iterator returns
+    /// each result along with the index of its image among the images passed in.
+    /// Mostly useful with [`Ordering::AsCompleted`], since failed compressions do not carry their index otherwise.
+    /// # Example
+    /// ```
+    /// use jippigy::{Ordering, Parallel};
+    /// fn main() {
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     let results = Parallel::from_vec(vector_of_bytes)
+    ///         .with_ordering(Ordering::AsCompleted)
+    ///         .build()
+    ///         .into_indexed_iter();
+    ///     for (index, result) in results {
+    ///         match result {
+    ///             Ok(output) => println!("{index}: {} bytes", output.bytes().len()),
+    ///             Err(e) => println!("{index}: {e}"),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn into_indexed_iter(self) -> ParallelIndexedIterator {
+        let receiver = self.receiver.clone();
+        let ordering = self.to_thread.ordering;
+        let abort = AbortHandle::default();
+        // images compressed ahead of their turn are held by the threads, the buffer or the iterator, never more than that.
+        let window = (ordering == Ordering::InOrder).then(|| {
+            let buffer = receiver.capacity().unwrap_or_default();
+            Arc::new(Window::new(self.to_thread.threads() + buffer))
+        });
+        let handles = self
+            .to_thread
+            .send_to_threads(self.transmitter, &abort, window.clone());
+        ParallelIndexedIterator::new(
+            receiver,
+            ordering,
+            Workers {
+                handles,
+                abort,
+                window,
+            },
+        )
+    }
+    /// Starts the compression like [`into_iter`](Parallel::into_iter()) does, but returns a [`Stream`](futures_core::Stream)
+    /// of compressed images, in the order given by [`with_ordering`](ParallelBuilder::with_ordering).
+    /// Must be called from within a tokio runtime: compressed images are waited for on tokio's blocking threads.
+    ///
+    /// Dropping the stream stops scheduling further images: the images the threads are compressing run to completion, and their results are discarded.
+    ///
+    /// Requires the `async` feature.
+    /// # Example
+    /// ```
+    /// use futures::StreamExt;
+    /// use jippigy::Parallel;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     let mut stream = Parallel::from_vec(vector_of_bytes).build().into_stream();
+    ///     while let Some(result) = stream.next().await {
+    ///         let _bytes: Vec<u8> = result?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn into_stream(self) -> crate::ParallelStream {
+        crate::ParallelStream::new(self.into_iter())
     }
 }
 impl Display for Parallel {
@@ -210,32 +744,228 @@ impl IntoIterator for Parallel {
     type Item = Result<Vec<u8>, error::Error>;
     type IntoIter = ParallelIntoIterator;
     fn into_iter(self) -> Self::IntoIter {
-        let receiver = self.receiver.clone();
-        let handles = self.compress();
-        ParallelIntoIterator::new(receiver, handles)
+        ParallelIntoIterator::new(self.into_output_iter())
     }
 }
 
 /// Target type when converting [`Parallel`] into an iterator.
-#[derive(Debug, Clone)]
+///
+/// Dropping the iterator stops the threads and waits for the images they are compressing to be done.
+#[derive(Debug)]
 pub struct ParallelIntoIterator {
-    recv: channel::Receiver<Result<Vec<u8>, error::Error>>,
+    outputs: ParallelOutputIterator,
 }
 impl ParallelIntoIterator {
-    fn new(
-        recv: channel::Receiver<Result<Vec<u8>, error::Error>>,
-        _handles: Vec<JoinHandle<()>>,
-    ) -> Self {
-        Self { recv }
+    fn new(outputs: ParallelOutputIterator) -> Self {
+        Self { outputs }
+    }
+    /// Stops the compression: threads stop taking new images and the iterator returns no more items.
+    /// See [`AbortHandle`].
+    pub fn abort(&self) {
+        self.outputs.abort();
+    }
+    /// Returns an [`AbortHandle`] to stop the compression from elsewhere, e.g. another thread.
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.outputs.abort_handle()
     }
 }
 impl Iterator for ParallelIntoIterator {
     /// A fallible containing compressed JPEG bytes.
     type Item = Result<Vec<u8>, error::Error>;
     fn next(&mut self) -> Option<Self::Item> {
-        if let Ok(result) = self.recv.recv() {
-            return Some(result);
+        self.outputs
+            .next()
+            .map(|result| result.map(CompressionOutput::into_bytes))
+    }
+}
+/// Target type of [`Parallel::into_output_iter`].
+///
+/// Dropping the iterator stops the threads and waits for the images they are compressing to be done.
+#[derive(Debug)]
+pub struct ParallelOutputIterator {
+    indexed: ParallelIndexedIterator,
+}
+impl ParallelOutputIterator {
+    fn new(indexed: ParallelIndexedIterator) -> Self {
+        Self { indexed }
+    }
+    /// Stops the compression: threads stop taking new images and the iterator returns no more items.
+    /// See [`AbortHandle`].
+    pub fn abort(&self) {
+        self.indexed.abort();
+    }
+    /// Returns an [`AbortHandle`] to stop the compression from elsewhere, e.g. another thread.
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.indexed.abort_handle()
+    }
+}
+impl Iterator for ParallelOutputIterator {
+    /// A fallible containing compressed JPEG bytes and details on how they were compressed.
+    type Item = Result<CompressionOutput, error::Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indexed.next().map(|(_index, result)| result)
+    }
+}
+/// How far ahead of the iterator threads may take images with [`Ordering::InOrder`].
+///
+/// Images are numbered in the order threads take them, so a thread taking an image only once
+/// fewer than `size` images are taken but not yet returned keeps every index below `returned + size`.
+#[derive(Debug)]
+struct Window {
+    /// Images taken by the threads, and images returned by the iterator.
+    counts: Mutex<(usize, usize)>,
+    returned: Condvar,
+    size: usize,
+}
+impl Window {
+    fn new(size: usize) -> Self {
+        Self {
+            counts: Mutex::new((0, 0)),
+            returned: Condvar::new(),
+            // a window of 0 would never let the first image in.
+            size: size.max(1),
+        }
+    }
+    /// Waits until an image can be taken without going past the window, and counts it as taken.
+    /// Returns `false` if the task is aborted meanwhile.
+    fn claim(&self, abort: &AbortHandle) -> bool {
+        let Ok(mut counts) = self.counts.lock() else {
+            return false;
+        };
+        while counts.0 >= counts.1 + self.size {
+            if abort.is_aborted() {
+                return false;
+            }
+            // an abort handle does not wake threads up, so they check it every now and then.
+            counts = match self
+                .returned
+                .wait_timeout(counts, Duration::from_millis(10))
+            {
+                Ok((counts, _timeout)) => counts,
+                Err(_) => return false,
+            };
+        }
+        counts.0 += 1;
+        true
+    }
+    /// Counts an image as returned by the iterator, letting a waiting thread take the next one.
+    fn advance(&self) {
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.1 += 1;
+        }
+        self.returned.notify_one();
+    }
+    /// Wakes up every waiting thread, e.g. to let them see that the task is aborted.
+    fn release(&self) {
+        let _counts = self.counts.lock();
+        self.returned.notify_all();
+    }
+}
+/// Threads compressing the images of a [`Parallel`].
+#[derive(Debug)]
+struct Workers {
+    handles: Vec<JoinHandle<()>>,
+    abort: AbortHandle,
+    window: Option<Arc<Window>>,
+}
+impl Drop for Workers {
+    /// Stops the threads once the iterator is dropped, and waits for them.
+    fn drop(&mut self) {
+        self.abort.abort();
+        if let Some(window) = &self.window {
+            window.release();
+        }
+        for handle in self.handles.drain(..) {
+            let _join = handle.join();
+        }
+    }
+}
+/// Target type of [`Parallel::into_indexed_iter`].
+///
+/// Dropping the iterator stops the threads and waits for the images they are compressing to be done.
+#[derive(Debug)]
+pub struct ParallelIndexedIterator {
+    // dropped before `workers`, so that threads waiting to send their images are released before being joined.
+    recv: channel::Receiver<(usize, Result<CompressionOutput, error::Error>)>,
+    ordering: Ordering,
+    /// Index of the next result to return in order.
+    next_index: usize,
+    /// Results received ahead of the next one, waiting for their turn. Bounded by the window of the threads.
+    pending: BTreeMap<usize, Result<CompressionOutput, error::Error>>,
+    workers: Workers,
+}
+impl ParallelIndexedIterator {
+    fn new(
+        recv: channel::Receiver<(usize, Result<CompressionOutput, error::Error>)>,
+        ordering: Ordering,
+        workers: Workers,
+    ) -> Self {
+        Self {
+            recv,
+            ordering,
+            next_index: 0,
+            pending: BTreeMap::new(),
+            workers,
+        }
+    }
+    /// Stops the compression: threads stop taking new images and the iterator returns no more items.
+    /// See [`AbortHandle`].
+    pub fn abort(&self) {
+        self.workers.abort.abort();
+        if let Some(window) = &self.workers.window {
+            window.release();
+        }
+    }
+    /// Returns an [`AbortHandle`] to stop the compression from elsewhere, e.g. another thread.
+    /// # Example
+    /// ```
+    /// use jippigy::Parallel;
+    /// fn main() {
+    ///     let mut vector_of_bytes: Vec<Vec<u8>> = Vec::new();
+    ///     let results = Parallel::from_vec(vector_of_bytes).build().into_indexed_iter();
+    ///     let abort = results.abort_handle();
+    ///     // e.g. when the user cancels an upload.
+    ///     std::thread::spawn(move || abort.abort());
+    ///     for (index, _result) in results {
+    ///         println!("{index} is done");
+    ///     }
+    /// }
+    /// ```
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.workers.abort.clone()
+    }
+}
+impl Iterator for ParallelIndexedIterator {
+    /// The index of an image among the images passed in, along with a fallible containing its compressed JPEG bytes.
+    type Item = (usize, Result<CompressionOutput, error::Error>);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ordering == Ordering::AsCompleted {
+            let result = self.recv.recv().ok();
+            return result.filter(|_| !self.workers.abort.is_aborted());
+        }
+        // threads never wait for each other, results that arrive early wait here instead.
+        loop {
+            if self.workers.abort.is_aborted() {
+                return None;
+            }
+            if let Some(result) = self.pending.remove(&self.next_index) {
+                self.next_index += 1;
+                if let Some(window) = &self.workers.window {
+                    window.advance();
+                }
+                return Some((self.next_index - 1, result));
+            }
+            match self.recv.recv() {
+                Ok((index, result)) => {
+                    self.pending.insert(index, result);
+                }
+                // every thread is done, return whatever is left in order.
+                Err(_) => {
+                    let (index, result) = self.pending.pop_first()?;
+                    self.next_index = index + 1;
+                    return Some((index, result));
+                }
+            }
         }
-        None
     }
 }
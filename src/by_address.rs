@@ -0,0 +1,50 @@
+use std::cmp::Ordering;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A value shared by reference, compared, ordered and hashed by its address instead of its contents.
+/// Used for values provided by the caller that cannot be compared otherwise, such as callbacks, iterators and thread pools,
+/// so that the builders holding them can still derive the usual traits.
+pub(crate) struct ByAddress<T: ?Sized>(pub(crate) Arc<T>);
+impl<T: ?Sized> ByAddress<T> {
+    fn address(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+}
+impl<T: ?Sized> Clone for ByAddress<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+impl<T: ?Sized> Deref for ByAddress<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: ?Sized> std::fmt::Debug for ByAddress<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ByAddress({:#x})", self.address())
+    }
+}
+impl<T: ?Sized> PartialEq for ByAddress<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+impl<T: ?Sized> Eq for ByAddress<T> {}
+impl<T: ?Sized> PartialOrd for ByAddress<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T: ?Sized> Ord for ByAddress<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.address().cmp(&other.address())
+    }
+}
+impl<T: ?Sized> std::hash::Hash for ByAddress<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
@@ -1,6 +1,16 @@
-use crate::error;
+use crate::{
+    ancillary, error, exif,
+    float::Float,
+    progress::{Callback, Tracker},
+    ssim, Ancillary, CompressionOutput, Filter, Lossless, Metadata, MetadataPolicy, Orientation,
+    Outcome, Resize, Subsampling, Warning, QUALITY,
+};
+use bytes::Bytes;
 use img_parts::{jpeg::Jpeg, ImageEXIF, ImageICC};
-use turbojpeg::{compress_image, decompress_image, Subsamp::Sub2x2};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Instant;
+use turbojpeg::{compress_image, decompress_image, Subsamp, Transform};
 
 #[derive(Debug, Clone, Copy)]
 struct ValidQuality(u8);
@@ -18,63 +28,320 @@ impl From<u8> for ValidQuality {
         Self(val)
     }
 }
+/// The fraction of the original size a compressed image must save, between 0.0 and 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub(crate) struct Margin(Float);
+impl Margin {
+    fn val(&self) -> f64 {
+        self.0 .0
+    }
+    /// Returns whether `compressed` bytes are smaller than `original` bytes by at least the margin.
+    fn is_met(&self, compressed: usize, original: usize) -> bool {
+        compressed < original && compressed as f64 <= original as f64 * (1.0 - self.val())
+    }
+}
+impl From<f64> for Margin {
+    fn from(value: f64) -> Self {
+        if value.is_nan() {
+            return Self(Float(0.0));
+        }
+        Self(Float(value.clamp(0.0, 1.0)))
+    }
+}
+impl Display for Margin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}%", self.val() * 100.0)
+    }
+}
+/// How the quality of compressed images is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub(crate) enum Mode {
+    /// Compress with the quality given in [`Settings`].
+    #[default]
+    Quality,
+    /// Search for the highest quality whose output fits within this many bytes.
+    TargetSize(usize),
+    /// Search for the lowest quality whose output is at least this similar to the original.
+    MinSimilarity(ssim::Score),
+    /// Rewrite the entropy coding only, without decoding the image.
+    Lossless(Lossless),
+}
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Quality => write!(f, "fixed quality"),
+            Mode::TargetSize(bytes) => write!(f, "target size of {bytes} bytes"),
+            Mode::MinSimilarity(score) => write!(f, "minimum similarity of {score}"),
+            Mode::Lossless(lossless) => write!(f, "lossless ({lossless})"),
+        }
+    }
+}
+/// Compression settings shared by every image of a compression task.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Settings {
+    pub(crate) quality: u8,
+    pub(crate) subsampling: Subsampling,
+    pub(crate) mode: Mode,
+    pub(crate) progressive: bool,
+    pub(crate) resize: Resize,
+    pub(crate) filter: Filter,
+    pub(crate) auto_orient: bool,
+    pub(crate) metadata: MetadataPolicy,
+    pub(crate) ancillary: Arc<[Ancillary]>,
+    pub(crate) skip_lower_quality: bool,
+    pub(crate) size_guard: Option<Margin>,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            quality: QUALITY,
+            subsampling: Subsampling::default(),
+            mode: Mode::default(),
+            progressive: false,
+            resize: Resize::default(),
+            filter: Filter::default(),
+            auto_orient: false,
+            metadata: MetadataPolicy::default(),
+            ancillary: Ancillary::ALL.into(),
+            skip_lower_quality: false,
+            size_guard: None,
+        }
+    }
+}
+impl Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "quality: {}\nsubsampling: {}\nmode: {}\nprogressive: {}\nresize: {} ({} filter)\nauto-orient: {}\nmetadata: {}\nancillary segments: {}\nskip if lower quality: {}\nsize guard: {}",
+            self.quality,
+            self.subsampling,
+            self.mode,
+            self.progressive,
+            self.resize,
+            self.filter,
+            self.auto_orient,
+            self.metadata,
+            ancillary::Kept(&self.ancillary),
+            self.skip_lower_quality,
+            self.size_guard
+                .map_or_else(|| "none".to_string(), |margin| format!("{margin} smaller"))
+        )
+    }
+}
 /// Compression-related work.
 #[derive(Debug, Clone)]
 pub(crate) struct Compress {
-    bytes: Vec<u8>,
-    quality: u8,
+    /// Shared with the outputs and passes that need the original image, instead of being copied for each of them.
+    bytes: Bytes,
+    settings: Settings,
+    /// Index of the image among the images passed in.
+    index: usize,
+    /// Reports each pass of the quality searches.
+    progress: Option<Callback>,
 }
 impl Compress {
     /// Creates a new compression task.
-    pub(crate) fn new(bytes: Vec<u8>, quality: u8) -> Self {
+    pub(crate) fn new(bytes: Vec<u8>, settings: Settings) -> Self {
         Self {
-            bytes,
-            quality: ValidQuality::from(quality).val(),
+            bytes: Bytes::from(bytes),
+            settings: Settings {
+                quality: ValidQuality::from(settings.quality).val(),
+                ..settings
+            },
+            index: 0,
+            progress: None,
         }
     }
+    /// Sets the index of the image among the images passed in.
+    pub(crate) fn with_index(self, index: usize) -> Self {
+        Self { index, ..self }
+    }
+    /// Sets the callback reporting each pass of the quality searches.
+    pub(crate) fn with_progress(self, progress: Option<Callback>) -> Self {
+        Self { progress, ..self }
+    }
     /// Compresses the image with [turbojpeg](https://github.com/honzasp/rust-turbojpeg) while preserving exif data.
-
-    pub(crate) fn compress(&self) -> Result<Vec<u8>, error::Error> {
-        let with_exif_preserved = CompressImage::new(self.bytes.clone(), self.quality)
-            .compress()?
-            .into_preserve_exif()
-            .preserve_exif()?;
-        // It must always be safe to unwrap this method.
-        // Otherwise exif is not preserved.
-        Ok(with_exif_preserved.get_compressed_bytes().unwrap())
+    /// The output records details on the original image and how long compression took.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "compress",
+            skip_all,
+            fields(index = self.index, size = self.bytes.len(), output_size = tracing::field::Empty)
+        )
+    )]
+    pub(crate) fn compress(self) -> Result<CompressionOutput, error::Error> {
+        let start = Instant::now();
+        let index = self.index;
+        let original = self.bytes.clone();
+        let output = self.compress_or_keep()?;
+        for warning in output.warnings() {
+            warning.log();
+        }
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("output_size", output.bytes().len());
+        Ok(output
+            .with_index(index)
+            .with_original(&original)
+            .with_duration(start.elapsed()))
+    }
+    /// Compresses the image, or keeps the original image as is.
+    ///
+    /// When skipping images of lower quality, the original image is kept if its estimated quality is lower than the requested quality
+    /// (searches are capped at its quality instead).
+    /// With a size guard (implied by skipping images of lower quality), the original image is kept if the compressed image is not smaller by its margin.
+    fn compress_or_keep(self) -> Result<CompressionOutput, error::Error> {
+        let quality = match self.settings.skip_lower_quality {
+            false => self.settings.quality,
+            true => match Metadata::from_shared(self.bytes.clone())?.quality() {
+                Some(source) if source < self.settings.quality => {
+                    if self.settings.mode == Mode::Quality {
+                        return Ok(CompressionOutput::kept(
+                            Vec::from(self.bytes),
+                            Outcome::SourceQualityLower,
+                        ));
+                    }
+                    source
+                }
+                _ => self.settings.quality,
+            },
+        };
+        let guard = self
+            .settings
+            .size_guard
+            .or(self.settings.skip_lower_quality.then(Margin::default));
+        let Some(margin) = guard else {
+            return self.encode();
+        };
+        let original = self.bytes.clone();
+        let output = Self {
+            settings: Settings {
+                quality,
+                ..self.settings
+            },
+            ..self
+        }
+        .encode()?;
+        if !margin.is_met(output.bytes().len(), original.len()) {
+            return Ok(CompressionOutput::kept(
+                Vec::from(original),
+                Outcome::NotSmaller,
+            ));
+        }
+        Ok(output)
+    }
+    /// Compresses the image according to the mode of its settings.
+    fn encode(self) -> Result<CompressionOutput, error::Error> {
+        match self.settings.mode {
+            Mode::Quality => {
+                let image = CompressImage::decode(self.bytes, &self.settings)?;
+                let (bytes, warnings) = image.compress_preserving_exif(self.settings.quality)?;
+                Ok(CompressionOutput::new(bytes, Some(self.settings.quality))
+                    .with_warnings(warnings))
+            }
+            Mode::TargetSize(target) => {
+                let progress = self.progress.map(|callback| {
+                    Tracker::new(callback, Some(search_passes(self.settings.quality)))
+                });
+                CompressImage::decode(self.bytes, &self.settings)?.search_target_size(
+                    target,
+                    self.settings.quality,
+                    progress.as_ref(),
+                )
+            }
+            Mode::MinSimilarity(threshold) => {
+                let progress = self.progress.map(|callback| {
+                    Tracker::new(callback, Some(search_passes(self.settings.quality)))
+                });
+                CompressImage::decode(self.bytes, &self.settings)?.search_min_similarity(
+                    threshold,
+                    self.settings.quality,
+                    progress.as_ref(),
+                )
+            }
+            Mode::Lossless(lossless) => {
+                let compressed_bytes = lossless.recompress(&self.bytes)?;
+                let with_exif_preserved = PreserveExif {
+                    original_bytes: self.bytes,
+                    compressed_bytes,
+                    with_exif_preserved: Vec::new(),
+                    reset_orientation: false,
+                    metadata: self.settings.metadata,
+                    ancillary: self.settings.ancillary,
+                    reencoded: false,
+                    warnings: Vec::new(),
+                }
+                .preserve_exif()?;
+                // It must always be safe to unwrap this method.
+                // Otherwise exif is not preserved.
+                let (bytes, warnings) = with_exif_preserved.get_compressed_bytes().unwrap();
+                Ok(CompressionOutput::new(bytes, None).with_warnings(warnings))
+            }
+        }
     }
 }
+/// Returns the most passes a binary search over qualities from 1 up to `max_quality` can take.
+fn search_passes(max_quality: u8) -> usize {
+    (u8::BITS - max_quality.leading_zeros()) as usize
+}
 /// Compress an image, retaining its bytes before and after compression.
 struct PreserveExif {
-    original_bytes: Vec<u8>,
+    original_bytes: Bytes,
     compressed_bytes: Vec<u8>,
     with_exif_preserved: Vec<u8>,
+    /// Whether the pixels have been rotated upright, so the EXIF Orientation tag must be reset.
+    reset_orientation: bool,
+    /// Which EXIF data is preserved.
+    metadata: MetadataPolicy,
+    /// Which ancillary segments are preserved.
+    ancillary: Arc<[Ancillary]>,
+    /// Whether the image has been re-encoded, as opposed to losslessly recompressed.
+    reencoded: bool,
+    /// Problems met while preserving EXIF data and ICC profiles.
+    warnings: Vec<Warning>,
 }
 impl PreserveExif {
     /// Using the bytes retained before and after compression,
     /// Parse EXIF information from the original bytes and write it
     /// into the compressed bytes.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "preserve",
+            skip_all,
+            fields(size = self.compressed_bytes.len())
+        )
+    )]
     fn preserve_exif(self) -> Result<Self, error::Error> {
-        let original_img_parts = match Jpeg::from_bytes(self.original_bytes.into()) {
+        let original_img_parts = match Jpeg::from_bytes(self.original_bytes) {
             Err(e) => return Err(error::Error::ImgPartError(e.to_string())),
             Ok(res) => res,
         };
-        let exif = match original_img_parts.exif().ok_or(error::Error::ImgPartError(
-            "Warning: No EXIF data found".to_string(),
-        )) {
-            Err(e) => {
-                eprintln!("{e}");
-                Vec::with_capacity(0).into()
+        let mut warnings = Vec::new();
+        let mut exif: Vec<u8> = match original_img_parts.exif().ok_or(Warning::NoExif) {
+            Err(warning) => {
+                warnings.push(warning);
+                Vec::with_capacity(0)
+            }
+            Ok(res) => res.to_vec(),
+        };
+        if self.reset_orientation {
+            exif::set_orientation(exif.as_mut_slice(), 1);
+        }
+        let exif = match self.metadata.apply(exif) {
+            Err(warning) => {
+                warnings.push(warning);
+                None
             }
             Ok(res) => res,
         };
         let icc_profile = match original_img_parts
             .icc_profile()
-            .ok_or(error::Error::ImgPartError(
-                "Warning: No ICC profile found".to_string(),
-            )) {
-            Err(e) => {
-                eprintln!("{e}");
+            .ok_or(Warning::NoIccProfile)
+        {
+            Err(warning) => {
+                warnings.push(warning);
                 Vec::with_capacity(0).into()
             }
             Ok(res) => res,
@@ -83,19 +350,31 @@ impl PreserveExif {
             Err(e) => return Err(error::Error::ImgPartError(e.to_string())),
             Ok(res) => res,
         };
-        compressed_img_part.set_exif(exif.into());
+        compressed_img_part.set_exif(exif.map(Into::into));
         compressed_img_part.set_icc_profile(icc_profile.into());
+        ancillary::preserve(
+            &original_img_parts,
+            &mut compressed_img_part,
+            &self.ancillary,
+            self.metadata.strips(),
+            self.reencoded,
+        );
         Ok(Self {
-            original_bytes: Vec::with_capacity(0),   // no longer needed
+            original_bytes: Bytes::new(),            // no longer needed
             compressed_bytes: Vec::with_capacity(0), // no longer needed
             with_exif_preserved: compressed_img_part.encoder().bytes().to_vec(),
+            reset_orientation: self.reset_orientation,
+            metadata: self.metadata,
+            ancillary: self.ancillary,
+            reencoded: self.reencoded,
+            warnings,
         })
     }
-    /// Returns the compressed bytes with EXIF preserved.
+    /// Returns the compressed bytes with EXIF preserved, along with the warnings raised while preserving it.
     /// Fails if EXIF has not been preserved yet.
-    fn get_compressed_bytes(self) -> Result<Vec<u8>, error::Error> {
+    fn get_compressed_bytes(self) -> Result<(Vec<u8>, Vec<Warning>), error::Error> {
         if self.compressed_bytes.is_empty() && !self.with_exif_preserved.is_empty() {
-            Ok(self.with_exif_preserved)
+            Ok((self.with_exif_preserved, self.warnings))
         } else {
             Err(error::Error::JippigyInternalError(
                 "BUG: EXIF data is not preserved.".to_string(),
@@ -103,39 +382,169 @@ impl PreserveExif {
         }
     }
 }
+/// A decoded image, ready to be compressed with any quality.
 struct CompressImage {
-    bytes: Vec<u8>,
-    compressed_bytes: Vec<u8>,
-    q: u8,
+    bytes: Bytes,
+    image: image::RgbImage,
+    subsamp: Subsamp,
+    progressive: bool,
+    /// Whether the image has been rotated upright.
+    oriented: bool,
+    metadata: MetadataPolicy,
+    ancillary: Arc<[Ancillary]>,
 }
 impl CompressImage {
-    /// Creates a new image to be compressed.
-    fn new(bytes: Vec<u8>, q: u8) -> Self {
-        Self {
-            q,
+    /// Decodes (resizes and orients) the image to be compressed, retaining its original bytes.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(size = bytes.len()))
+    )]
+    fn decode(bytes: Bytes, settings: &Settings) -> Result<Self, error::Error> {
+        let subsamp = settings.subsampling.resolve(&bytes)?;
+        let orientation = if settings.auto_orient {
+            Orientation::read(&bytes)?
+        } else {
+            Orientation::Normal
+        };
+        // maximum dimensions apply to the upright image.
+        let resize = if orientation.is_transposed() {
+            settings.resize.transposed()
+        } else {
+            settings.resize
+        };
+        let image = resize.decode(&bytes, settings.filter)?;
+        Ok(Self {
             bytes,
-            compressed_bytes: Default::default(),
-        }
+            image: orientation.apply(image),
+            subsamp,
+            progressive: settings.progressive,
+            oriented: orientation != Orientation::Normal,
+            metadata: settings.metadata.clone(),
+            ancillary: Arc::clone(&settings.ancillary),
+        })
     }
-    /// Compresses image file, retaining original and compressed bytes. Returns self.
-    fn compress(mut self) -> Result<Self, error::Error> {
-        let image: image::RgbImage = match decompress_image(self.bytes.as_slice()) {
-            Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
-            Ok(res) => res,
-        };
-        let jpeg_data = match compress_image(&image, i32::from(self.q), Sub2x2) {
+    /// Compresses the decoded image with quality `q`, as a progressive JPEG if requested. Produce PreserveExif.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "encode",
+            skip_all,
+            fields(quality = q, output_size = tracing::field::Empty)
+        )
+    )]
+    fn compress(&self, q: u8) -> Result<PreserveExif, error::Error> {
+        let mut jpeg_data = match compress_image(&self.image, i32::from(q), self.subsamp) {
             Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
             Ok(res) => res,
         };
-        self.compressed_bytes = jpeg_data.to_vec();
-        Ok(self)
-    }
-    /// Produce PreserveExif.
-    fn into_preserve_exif(self) -> PreserveExif {
-        PreserveExif {
-            original_bytes: self.bytes,
-            compressed_bytes: self.compressed_bytes,
+        if self.progressive {
+            // losslessly rewrite the baseline output into a progressive one.
+            let mut transform = Transform::default();
+            transform.progressive = true;
+            jpeg_data = match turbojpeg::transform(&transform, &jpeg_data) {
+                Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
+                Ok(res) => res,
+            };
+        }
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("output_size", jpeg_data.len());
+        Ok(PreserveExif {
+            // only the reference count is increased, the original bytes are not copied.
+            original_bytes: self.bytes.clone(),
+            compressed_bytes: jpeg_data.to_vec(),
             with_exif_preserved: Vec::new(),
+            reset_orientation: self.oriented,
+            metadata: self.metadata.clone(),
+            ancillary: Arc::clone(&self.ancillary),
+            reencoded: true,
+            warnings: Vec::new(),
+        })
+    }
+    /// Compresses the decoded image with quality `q` while preserving exif data, returning the warnings raised while preserving it.
+    fn compress_preserving_exif(&self, q: u8) -> Result<(Vec<u8>, Vec<Warning>), error::Error> {
+        let with_exif_preserved = self.compress(q)?.preserve_exif()?;
+        // It must always be safe to unwrap this method.
+        // Otherwise exif is not preserved.
+        Ok(with_exif_preserved.get_compressed_bytes().unwrap())
+    }
+    /// Binary searches qualities from 1 up to `max_quality` for the highest quality
+    /// whose output (exif data included) is at most `target` bytes.
+    fn search_target_size(
+        &self,
+        target: usize,
+        max_quality: u8,
+        progress: Option<&Tracker>,
+    ) -> Result<CompressionOutput, error::Error> {
+        let mut low = 1;
+        let mut high = max_quality;
+        let mut smallest = usize::MAX;
+        let mut best = None;
+        while low <= high {
+            let q = low + (high - low) / 2;
+            let (bytes, warnings) = self.compress_preserving_exif(q)?;
+            if let Some(progress) = progress {
+                progress.record(self.bytes.len(), bytes.len());
+            }
+            smallest = std::cmp::min(smallest, bytes.len());
+            if bytes.len() <= target {
+                best = Some(CompressionOutput::new(bytes, Some(q)).with_warnings(warnings));
+                low = q + 1;
+            } else {
+                high = q - 1;
+            }
         }
+        best.ok_or_else(|| {
+            error::Error::TargetError(format!(
+                "Cannot compress to {target} bytes or less, the smallest output is {smallest} bytes."
+            ))
+        })
+    }
+    /// Binary searches qualities from 1 up to `max_quality` for the lowest quality
+    /// whose decoded output has a [`ssim`] score of at least `threshold` against the decoded original.
+    fn search_min_similarity(
+        &self,
+        threshold: ssim::Score,
+        max_quality: u8,
+        progress: Option<&Tracker>,
+    ) -> Result<CompressionOutput, error::Error> {
+        let mut low = 1;
+        let mut high = max_quality;
+        let mut highest = ssim::Score::from(0.0);
+        let mut best = None;
+        // the original does not change between passes, only the compressed image does.
+        let reference = ssim::Reference::new(&self.image);
+        while low <= high {
+            let q = low + (high - low) / 2;
+            let compressed = self.compress(q)?;
+            let decoded: image::RgbImage =
+                match decompress_image(compressed.compressed_bytes.as_slice()) {
+                    Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
+                    Ok(res) => res,
+                };
+            let score = reference.ssim(&decoded);
+            if let Some(progress) = progress {
+                progress.record(self.bytes.len(), compressed.compressed_bytes.len());
+            }
+            highest = std::cmp::max(highest, score);
+            if score >= threshold {
+                // It must always be safe to unwrap this method.
+                // Otherwise exif is not preserved.
+                let (bytes, warnings) = compressed.preserve_exif()?.get_compressed_bytes().unwrap();
+                best = Some(
+                    CompressionOutput::new(bytes, Some(q))
+                        .with_similarity(score.val())
+                        .with_warnings(warnings),
+                );
+                high = q - 1;
+            } else {
+                low = q + 1;
+            }
+        }
+        best.ok_or_else(|| {
+            error::Error::TargetError(format!(
+                "Cannot reach a similarity of {threshold}, the highest similarity is {highest}."
+            ))
+        })
     }
 }
@@ -2,3 +2,5 @@
 pub(crate) const QUALITY: u8 = 95;
 /// Default number of threads used.
 pub(crate) const DEVICE: u8 = 2;
+/// Default number of compressed images waiting to be consumed.
+pub(crate) const BUFFER: usize = 8;
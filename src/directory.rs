@@ -0,0 +1,417 @@
+use crate::{
+    by_address::ByAddress,
+    error,
+    input::{Input, Lazy},
+    CompressionOutput, Outcome, Parallel, ParallelBuilder, Warning,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Extensions of the files compressed by default, compared case-insensitively.
+const EXTENSIONS: &[&str] = &["jpg", "jpeg"];
+
+/// Configures the [`ParallelBuilder`] a [`Directory`] is compressed with.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Configure(ByAddress<dyn Fn(ParallelBuilder) -> ParallelBuilder + Send + Sync>);
+/// Custom configuration for building a [`Directory`].
+/// This struct is not meant to be used directly.
+/// Use [`Directory::from_paths`] instead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DirectoryBuilder {
+    input: PathBuf,
+    output: PathBuf,
+    recursive: bool,
+    extensions: Arc<[String]>,
+    configure: Option<Configure>,
+}
+impl DirectoryBuilder {
+    /// Builds a new [`Directory`] with default or specified configuration.
+    pub fn build(self) -> Directory {
+        Directory {
+            input: self.input,
+            output: self.output,
+            recursive: self.recursive,
+            extensions: self.extensions,
+            configure: self.configure,
+        }
+    }
+    /// Specifies whether the subdirectories of the input directory are compressed too, into the same subdirectories of the output directory.
+    /// Defaults to `false`.
+    ///
+    /// **This method is optional**.
+    pub fn with_recursive(self, recursive: bool) -> DirectoryBuilder {
+        DirectoryBuilder { recursive, ..self }
+    }
+    /// Specifies the extensions of the files to compress, without the leading dot. They are compared case-insensitively.
+    /// Defaults to `["jpg", "jpeg"]`, which also matches `.JPG` files. Other files are left out.
+    ///
+    /// **This method is optional**.
+    pub fn with_extensions(
+        self,
+        extensions: impl IntoIterator<Item = impl Into<String>>,
+    ) -> DirectoryBuilder {
+        DirectoryBuilder {
+            extensions: extensions.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+    /// Configures how images are compressed, with the same methods as [`ParallelBuilder`] (quality, threads, etc.).
+    /// The images are always the files of the input directory, whatever images the returned builder holds.
+    ///
+    /// **This method is optional**.
+    /// # Example
+    /// ```
+    /// use jippigy::Directory;
+    /// # const TEST_DIR: &str = "./tests/images/";
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let tempdir = tempdir::TempDir::new("compressed")?;
+    ///     let _reports = Directory::from_paths(TEST_DIR, tempdir.path())
+    ///         .with_parallel(|parallel| parallel.with_quality(80).with_device(4))
+    ///         .build()
+    ///         .compress()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_parallel(
+        self,
+        configure: impl Fn(ParallelBuilder) -> ParallelBuilder + Send + Sync + 'static,
+    ) -> DirectoryBuilder {
+        DirectoryBuilder {
+            configure: Some(Configure(ByAddress(Arc::new(configure)))),
+            ..self
+        }
+    }
+}
+impl Display for DirectoryBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "input: {}\noutput: {}\nrecursive: {}\nextensions: {}",
+            self.input.display(),
+            self.output.display(),
+            self.recursive,
+            self.extensions.join(", ")
+        )
+    }
+}
+/// Compressions of every JPEG file in a directory into another directory.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Directory {
+    input: PathBuf,
+    output: PathBuf,
+    recursive: bool,
+    extensions: Arc<[String]>,
+    configure: Option<Configure>,
+}
+impl Directory {
+    /// Creates a compression task from the files of the `input` directory to the `output` directory. Returns a [`DirectoryBuilder`].
+    ///
+    /// This method initializes the compression task with the following defaults:
+    /// - Only the files directly in the input directory are compressed, not those in its subdirectories.
+    /// - Files with a `.jpg` or `.jpeg` extension (in any case) are compressed.
+    /// - Images are compressed with the defaults of [`Parallel::from_vec`].
+    /// # Example
+    /// ```
+    /// use jippigy::Directory;
+    /// # const TEST_DIR: &str = "./tests/images/";
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     // this temporary directory is here for doctest purposes,
+    ///     // but you will create your own directory.
+    ///     let tempdir = tempdir::TempDir::new("compressed")?;
+    ///     let reports = Directory::from_paths(TEST_DIR, tempdir.path())
+    ///         .with_recursive(true)
+    ///         .build()
+    ///         .compress()?;
+    ///     for report in reports {
+    ///         match report.error() {
+    ///             None => println!("saved: {}", report.output().display()),
+    ///             Some(e) => println!("{}: {e}", report.input().display()),
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_paths(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> DirectoryBuilder {
+        DirectoryBuilder {
+            input: input.into(),
+            output: output.into(),
+            recursive: false,
+            extensions: EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            configure: None,
+        }
+    }
+    /// Compresses every file of the input directory, mirroring its structure in the output directory.
+    /// Returns a [`FileReport`] for each file, in the order of their paths.
+    ///
+    /// Files are read as threads become available to compress them, and each compressed image is written to a temporary file
+    /// that is then renamed, so that the output directory never holds partially written images.
+    ///
+    /// Fails with [`Error::IoError`](crate::Error::IoError) only if the input directory cannot be read,
+    /// or if the output directory is the input directory (or inside it, with [`with_recursive`](DirectoryBuilder::with_recursive)),
+    /// which would overwrite the originals or compress the compressed images again.
+    /// Failures of single files, or of subdirectories that cannot be read, are reported by their [`FileReport`] instead.
+    pub fn compress(self) -> Result<Vec<FileReport>, error::Error> {
+        self.check_output()?;
+        let (paths, unreadable) = self.list()?;
+        // read failures are reported in place of the result of their file.
+        let read_errors = Arc::new(Mutex::new(HashMap::new()));
+        let local_read_errors = Arc::clone(&read_errors);
+        let input = self.input.clone();
+        let files = paths
+            .clone()
+            .into_iter()
+            .enumerate()
+            .map(move |(index, path)| {
+                fs::read(input.join(path)).unwrap_or_else(|e| {
+                    if let Ok(mut read_errors) = local_read_errors.lock() {
+                        read_errors.insert(index, error::Error::IoError(e.to_string()));
+                    }
+                    Vec::new()
+                })
+            });
+        let builder = match &self.configure {
+            Some(configure) => (configure.0)(Parallel::from_vec(Vec::new())),
+            None => Parallel::from_vec(Vec::new()),
+        };
+        // set after configuring, so that indexes always match `paths`.
+        let builder = builder.with_input(Input::Lazy(Lazy::new(files)));
+        let mut reports = Vec::with_capacity(paths.len() + unreadable.len());
+        for (path, e) in unreadable {
+            let output = self.output.join(&path);
+            reports.push(FileReport::new(self.input.join(path), output, Err(e)));
+        }
+        for (index, result) in builder.build().into_indexed_iter() {
+            let input = self.input.join(&paths[index]);
+            let output = self.output.join(&paths[index]);
+            let read_error = read_errors.lock().ok().and_then(|mut e| e.remove(&index));
+            let result = match read_error {
+                Some(e) => Err(e),
+                None => result.and_then(|compressed| {
+                    write_atomically(&output, compressed.bytes()).map(|()| compressed)
+                }),
+            };
+            reports.push(FileReport::new(input, output, result));
+        }
+        reports.sort_by(|a, b| a.input.cmp(&b.input));
+        Ok(reports)
+    }
+    /// Fails if compressed images would be written over the originals, or among the images to compress.
+    fn check_output(&self) -> Result<(), error::Error> {
+        let io_error = |path: &Path, e: std::io::Error| {
+            error::Error::IoError(format!("{}: {e}", path.display()))
+        };
+        let input = fs::canonicalize(&self.input).map_err(|e| io_error(&self.input, e))?;
+        let output = resolve(&self.output).map_err(|e| io_error(&self.output, e))?;
+        if output == input {
+            return Err(error::Error::IoError(format!(
+                "The output directory {} is the input directory.",
+                self.output.display()
+            )));
+        }
+        if self.recursive && output.starts_with(&input) {
+            return Err(error::Error::IoError(format!(
+                "The output directory {} is inside the input directory, which is compressed recursively.",
+                self.output.display()
+            )));
+        }
+        Ok(())
+    }
+    /// Lists the files to compress, relative to the input directory,
+    /// along with the subdirectories and entries that cannot be read and why.
+    #[allow(clippy::type_complexity)]
+    fn list(&self) -> Result<(Vec<PathBuf>, Vec<(PathBuf, error::Error)>), error::Error> {
+        let mut paths = Vec::new();
+        let mut unreadable = Vec::new();
+        let mut directories = vec![PathBuf::new()];
+        while let Some(directory) = directories.pop() {
+            let entries = match fs::read_dir(self.input.join(&directory)) {
+                // only the input directory itself fails the whole compression.
+                Err(e) if directory.as_os_str().is_empty() => {
+                    return Err(error::Error::IoError(format!(
+                        "{}: {e}",
+                        self.input.display()
+                    )))
+                }
+                Err(e) => {
+                    unreadable.push((directory, error::Error::IoError(e.to_string())));
+                    continue;
+                }
+                Ok(res) => res,
+            };
+            for entry in entries {
+                let entry = match entry {
+                    Err(e) => {
+                        unreadable.push((directory.clone(), error::Error::IoError(e.to_string())));
+                        continue;
+                    }
+                    Ok(res) => res,
+                };
+                let path = directory.join(entry.file_name());
+                // symbolic links to directories are not followed, to avoid cycles.
+                match entry.file_type() {
+                    Err(e) => unreadable.push((path, error::Error::IoError(e.to_string()))),
+                    Ok(file_type) if file_type.is_dir() => {
+                        if self.recursive {
+                            directories.push(path);
+                        }
+                    }
+                    Ok(_) => {
+                        if entry.path().is_file() && self.has_extension(&path) {
+                            paths.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        paths.sort();
+        Ok((paths, unreadable))
+    }
+    /// Whether the file has one of the extensions to compress.
+    fn has_extension(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+            return false;
+        };
+        self.extensions
+            .iter()
+            .any(|wanted| wanted.eq_ignore_ascii_case(extension))
+    }
+}
+impl Display for Directory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "input: {}\noutput: {}\nrecursive: {}\nextensions: {}",
+            self.input.display(),
+            self.output.display(),
+            self.recursive,
+            self.extensions.join(", ")
+        )
+    }
+}
+/// Resolves `path` like [`fs::canonicalize`] does, even if its last components do not exist yet.
+fn resolve(path: &Path) -> std::io::Result<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        match fs::canonicalize(existing) {
+            Ok(resolved) => {
+                return Ok(missing
+                    .iter()
+                    .rev()
+                    .fold(resolved, |resolved, name| resolved.join(name)))
+            }
+            Err(e) => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e);
+                };
+                missing.push(name);
+                existing = match parent.as_os_str().is_empty() {
+                    true => Path::new("."),
+                    false => parent,
+                };
+            }
+        }
+    }
+}
+/// Writes `bytes` to a temporary file next to `path`, then renames it to `path`, creating its parent directories as needed.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), error::Error> {
+    let io_error = |e: std::io::Error| error::Error::IoError(format!("{}: {e}", path.display()));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".jippigy-tmp");
+    let temporary = PathBuf::from(temporary);
+    if let Err(e) = fs::write(&temporary, bytes).and_then(|()| fs::rename(&temporary, path)) {
+        let _remove = fs::remove_file(&temporary);
+        return Err(io_error(e));
+    }
+    Ok(())
+}
+/// What happened to a file compressed by [`Directory::compress`].
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    input: PathBuf,
+    output: PathBuf,
+    original_size: usize,
+    compressed_size: usize,
+    outcome: Option<Outcome>,
+    warnings: Vec<Warning>,
+    error: Option<error::Error>,
+}
+impl FileReport {
+    fn new(
+        input: PathBuf,
+        output: PathBuf,
+        result: Result<CompressionOutput, error::Error>,
+    ) -> Self {
+        let (original_size, compressed_size, outcome, warnings, error) = match result {
+            Ok(compressed) => (
+                compressed.original_size(),
+                compressed.bytes().len(),
+                Some(compressed.outcome()),
+                compressed.warnings().to_vec(),
+                None,
+            ),
+            Err(e) => (0, 0, None, Vec::new(), Some(e)),
+        };
+        Self {
+            input,
+            output,
+            original_size,
+            compressed_size,
+            outcome,
+            warnings,
+            error,
+        }
+    }
+    /// Returns the path of the original file.
+    pub fn input(&self) -> &Path {
+        self.input.as_path()
+    }
+    /// Returns the path the compressed file is written to, in the output directory.
+    pub fn output(&self) -> &Path {
+        self.output.as_path()
+    }
+    /// Returns the size of the original file in bytes, or 0 if it could not be compressed.
+    pub fn original_size(&self) -> usize {
+        self.original_size
+    }
+    /// Returns the size of the written file in bytes, or 0 if it could not be compressed.
+    pub fn compressed_size(&self) -> usize {
+        self.compressed_size
+    }
+    /// Returns whether the image has been compressed or kept as is, or `None` if it could not be compressed.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+    /// Returns the problems that did not prevent the image from being compressed, e.g. a missing ICC profile.
+    pub fn warnings(&self) -> &[Warning] {
+        self.warnings.as_slice()
+    }
+    /// Returns why the file could not be read, compressed or written, if so. Nothing is written to the output directory then.
+    pub fn error(&self) -> Option<&error::Error> {
+        self.error.as_ref()
+    }
+}
+impl Display for FileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            Some(e) => write!(f, "{}: {e}", self.input.display()),
+            None => write!(
+                f,
+                "{} -> {}: {} bytes -> {} bytes",
+                self.input.display(),
+                self.output.display(),
+                self.original_size,
+                self.compressed_size
+            ),
+        }
+    }
+}
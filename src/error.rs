@@ -12,4 +12,27 @@ pub enum Error {
     /// Error occured while attempting to read or write EXIF data and/or ICC profiles. See [img_part](https://github.com/paolobarbolini/img-parts)'s error [enumerations](https://docs.rs/img-parts/latest/img_parts/enum.Error.html).
     #[error("{0}")]
     ImgPartError(String),
+    /// Error occured while reading or rewriting the structure of JPEG data (its markers, Huffman tables, etc.).
+    #[error("JpegError:\n{0}")]
+    JpegError(String),
+    /// The requested target (e.g. a file size) cannot be reached with any quality.
+    #[error("TargetError:\n{0}")]
+    TargetError(String),
+    /// Error occured while reading or writing files or directories.
+    #[error("IoError:\n{0}")]
+    IoError(String),
+    /// A thread of [`Parallel`](crate::Parallel) panicked while compressing the image at `index`. Other images are not affected.
+    /// If you see it, please open an [issue](https://github.com/rfdzan/jippigy/issues).
+    #[error("A thread panicked while compressing image {index}.")]
+    WorkerPanicked {
+        /// The index of the image among the images passed in.
+        index: usize,
+    },
+    /// The iterator given to [`Parallel::from_iter`](crate::Parallel::from_iter) panicked while pulling the image at `index`.
+    /// No more images are pulled from it after that.
+    #[error("The input iterator panicked while pulling image {index}.")]
+    InputPanicked {
+        /// The index the image would have had among the images passed in.
+        index: usize,
+    },
 }
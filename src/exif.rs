@@ -0,0 +1,447 @@
+// EXIF data is a TIFF structure: the contents of the APP1 segment, without its `Exif\0\0` prefix.
+
+use std::ops::Range;
+
+/// EXIF tags commonly used with [`MetadataPolicy`](crate::MetadataPolicy).
+///
+/// Any other tag can be used as well, see the [list of EXIF tags](https://exiftool.org/TagNames/EXIF.html).
+pub mod tag {
+    /// Title of the image.
+    pub const IMAGE_DESCRIPTION: u16 = 0x010E;
+    /// Manufacturer of the camera.
+    pub const MAKE: u16 = 0x010F;
+    /// Model of the camera.
+    pub const MODEL: u16 = 0x0110;
+    /// Orientation of the image.
+    pub const ORIENTATION: u16 = 0x0112;
+    /// Software used to create the image.
+    pub const SOFTWARE: u16 = 0x0131;
+    /// Date and time the file was last modified.
+    pub const DATE_TIME: u16 = 0x0132;
+    /// Name of the photographer.
+    pub const ARTIST: u16 = 0x013B;
+    /// Copyright notice.
+    pub const COPYRIGHT: u16 = 0x8298;
+    /// Pointer to the EXIF IFD, which holds most capture details.
+    pub const EXIF_IFD: u16 = 0x8769;
+    /// Pointer to the GPS IFD, which holds the location the image was captured at.
+    pub const GPS_INFO: u16 = 0x8825;
+    /// Date and time the image was captured.
+    pub const DATE_TIME_ORIGINAL: u16 = 0x9003;
+    /// Date and time the image was digitized.
+    pub const DATE_TIME_DIGITIZED: u16 = 0x9004;
+    /// Manufacturer-specific data, often including serial numbers.
+    pub const MAKER_NOTE: u16 = 0x927C;
+    /// Comments of the user.
+    pub const USER_COMMENT: u16 = 0x9286;
+    /// Pointer to the Interoperability IFD.
+    pub const INTEROPERABILITY_IFD: u16 = 0xA005;
+    /// Unique identifier of the image.
+    pub const IMAGE_UNIQUE_ID: u16 = 0xA420;
+    /// Name of the owner of the camera.
+    pub const CAMERA_OWNER_NAME: u16 = 0xA430;
+    /// Serial number of the camera body.
+    pub const BODY_SERIAL_NUMBER: u16 = 0xA431;
+    /// Serial number of the lens.
+    pub const LENS_SERIAL_NUMBER: u16 = 0xA435;
+    /// Offset of the JPEG thumbnail, in IFD1.
+    pub(crate) const THUMBNAIL_OFFSET: u16 = 0x0201;
+    /// Length of the JPEG thumbnail, in IFD1.
+    pub(crate) const THUMBNAIL_LENGTH: u16 = 0x0202;
+}
+/// TIFF field type of NUL-terminated ASCII strings.
+const ASCII: u16 = 2;
+/// TIFF field type of unsigned 16 bit integers.
+const SHORT: u16 = 3;
+/// TIFF field type of unsigned 32 bit integers.
+const LONG: u16 = 4;
+/// TIFF field type of fractions of two unsigned 32 bit integers.
+const RATIONAL: u16 = 5;
+/// TIFF field type of offsets to IFDs.
+const IFD: u16 = 13;
+/// Size in bytes of the TIFF header.
+const HEADER: usize = 8;
+/// Sub-IFDs nested deeper than this are not read.
+const MAX_DEPTH: usize = 4;
+/// IFDs chained after this many are not read.
+const MAX_CHAIN: usize = 8;
+
+/// Whether the tag is a pointer to a sub-IFD.
+pub(crate) fn is_pointer(tag: u16) -> bool {
+    matches!(
+        tag,
+        tag::EXIF_IFD | tag::GPS_INFO | tag::INTEROPERABILITY_IFD
+    )
+}
+/// Size in bytes of a single value of a TIFF field type.
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+/// Byte order of TIFF data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    /// `II`, little-endian.
+    Little,
+    /// `MM`, big-endian.
+    Big,
+}
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8], pos: usize) -> Option<u16> {
+        let bytes: [u8; 2] = bytes.get(pos..pos + 2)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        })
+    }
+    fn u32(&self, bytes: &[u8], pos: usize) -> Option<u32> {
+        let bytes: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        })
+    }
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+/// Reads the TIFF header, returning the byte order and the offset of the first IFD.
+fn header(tiff: &[u8]) -> Option<(ByteOrder, usize)> {
+    let order = match tiff.get(..2)? {
+        b"II" => ByteOrder::Little,
+        b"MM" => ByteOrder::Big,
+        _ => return None,
+    };
+    if order.u16(tiff, 2)? != 42 {
+        return None;
+    }
+    Some((order, order.u32(tiff, 4)? as usize))
+}
+/// Finds the position of the value of a SHORT `tag` of the first IFD.
+fn short_position(tiff: &[u8], tag: u16) -> Option<(ByteOrder, usize)> {
+    let (order, ifd) = header(tiff)?;
+    let count = order.u16(tiff, ifd)?;
+    (0..usize::from(count))
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&entry| order.u16(tiff, entry) == Some(tag))
+        .filter(|&entry| order.u16(tiff, entry + 2) == Some(SHORT))
+        // a single SHORT is stored within the entry, at the start of its value field.
+        .map(|entry| (order, entry + 8))
+        .filter(|&(_, pos)| pos + 2 <= tiff.len())
+}
+/// Reads the Orientation tag, if any.
+pub(crate) fn orientation(tiff: &[u8]) -> Option<u16> {
+    let (order, pos) = short_position(tiff, tag::ORIENTATION)?;
+    order.u16(tiff, pos)
+}
+/// Sets the Orientation tag to `value` in place. Does nothing if there is no Orientation tag.
+pub(crate) fn set_orientation(tiff: &mut [u8], value: u16) {
+    if let Some((order, pos)) = short_position(tiff, tag::ORIENTATION) {
+        tiff[pos..pos + 2].copy_from_slice(&order.u16_bytes(value));
+    }
+}
+/// What to do with a field when filtering EXIF data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Retain {
+    /// Keep the field, along with its whole sub-IFD if it points to one.
+    Keep,
+    /// Remove the field, along with its whole sub-IFD if it points to one.
+    Remove,
+    /// Filter the sub-IFD the field points to, and keep the field only if some fields of the sub-IFD are kept.
+    /// Other fields are kept.
+    Filter,
+}
+/// Value of a TIFF field.
+#[derive(Debug, Clone)]
+enum Value {
+    /// Raw bytes of the value, in the byte order of the EXIF data.
+    Bytes(Vec<u8>),
+    /// Sub-IFD pointed to by the field.
+    Ifd(Ifd),
+    /// JPEG thumbnail pointed to by the field.
+    Thumbnail(Vec<u8>),
+    /// Maker note, written back at its original offset: it is an undocumented structure whose own offsets
+    /// may be relative to the start of the EXIF data, so it cannot be relocated.
+    MakerNote { offset: usize, bytes: Vec<u8> },
+}
+/// A field (or entry) of an IFD.
+#[derive(Debug, Clone)]
+struct Field {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value: Value,
+}
+/// An Image File Directory, a list of fields.
+#[derive(Debug, Clone, Default)]
+struct Ifd {
+    fields: Vec<Field>,
+}
+impl Ifd {
+    /// Reads the IFD at `offset` and its sub-IFDs.
+    /// Returns the IFD and the offset of the next IFD in the chain (0 if none).
+    fn parse(tiff: &[u8], order: ByteOrder, offset: usize, depth: usize) -> Option<(Ifd, usize)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let count = usize::from(order.u16(tiff, offset)?);
+        let entries = offset + 2;
+        let thumbnail_length = (0..count)
+            .map(|i| entries + 12 * i)
+            .find(|&entry| order.u16(tiff, entry) == Some(tag::THUMBNAIL_LENGTH))
+            .and_then(|entry| match order.u16(tiff, entry + 2)? {
+                SHORT => order.u16(tiff, entry + 8).map(u32::from),
+                _ => order.u32(tiff, entry + 8),
+            });
+        let mut fields = Vec::with_capacity(count);
+        for entry in (0..count).map(|i| entries + 12 * i) {
+            let tag = order.u16(tiff, entry)?;
+            let field_type = order.u16(tiff, entry + 2)?;
+            let count = order.u32(tiff, entry + 4)?;
+            let points = count == 1 && matches!(field_type, LONG | IFD);
+            let value = if is_pointer(tag) && points {
+                let sub = order.u32(tiff, entry + 8)? as usize;
+                Value::Ifd(Ifd::parse(tiff, order, sub, depth + 1)?.0)
+            } else if tag == tag::THUMBNAIL_OFFSET && points && thumbnail_length.is_some() {
+                let start = order.u32(tiff, entry + 8)? as usize;
+                let length = thumbnail_length? as usize;
+                Value::Thumbnail(tiff.get(start..start.checked_add(length)?)?.to_vec())
+            } else {
+                let Some(size) = type_size(field_type) else {
+                    // fields of unknown types cannot be relocated.
+                    continue;
+                };
+                let length = size.checked_mul(count as usize)?;
+                let start = if length <= 4 {
+                    entry + 8
+                } else {
+                    order.u32(tiff, entry + 8)? as usize
+                };
+                let bytes = tiff.get(start..start.checked_add(length)?)?.to_vec();
+                // a maker note overlapping the TIFF header cannot be kept in place.
+                if tag == tag::MAKER_NOTE && length > 4 && start >= HEADER {
+                    Value::MakerNote {
+                        offset: start,
+                        bytes,
+                    }
+                } else {
+                    Value::Bytes(bytes)
+                }
+            };
+            fields.push(Field {
+                tag,
+                field_type,
+                count,
+                value,
+            });
+        }
+        let next = order.u32(tiff, entries + 12 * count)? as usize;
+        Some((Ifd { fields }, next))
+    }
+    /// Finds the field with `tag`.
+    fn field(&self, tag: u16) -> Option<&Field> {
+        self.fields.iter().find(|field| field.tag == tag)
+    }
+    /// Finds the sub-IFD pointed to by the field with `tag`.
+    fn sub(&self, tag: u16) -> Option<&Ifd> {
+        match &self.field(tag)?.value {
+            Value::Ifd(sub) => Some(sub),
+            _ => None,
+        }
+    }
+    /// Collects the maker notes of this IFD and its sub-IFDs, with the range they were read from.
+    fn maker_notes<'a>(&'a self, notes: &mut Vec<(Range<usize>, &'a [u8])>) {
+        for field in self.fields.iter() {
+            match &field.value {
+                Value::MakerNote { offset, bytes } => {
+                    notes.push((*offset..offset + bytes.len(), bytes));
+                }
+                Value::Ifd(sub) => sub.maker_notes(notes),
+                _ => {}
+            }
+        }
+    }
+    /// Filters fields according to `retain`, returning whether any field is left.
+    fn retain(&mut self, retain: &impl Fn(u16) -> Retain) -> bool {
+        self.fields
+            .retain_mut(|field| match (retain(field.tag), &mut field.value) {
+                (Retain::Keep, _) => true,
+                (Retain::Remove, _) => false,
+                (Retain::Filter, Value::Ifd(sub)) => sub.retain(retain),
+                (Retain::Filter, _) => true,
+            });
+        !self.fields.is_empty()
+    }
+    /// Appends this IFD, its values and sub-IFDs to `out`, around the `reserved` ranges of maker notes.
+    /// Returns its offset and the position of its next IFD offset.
+    fn write(
+        &self,
+        order: ByteOrder,
+        out: &mut Vec<u8>,
+        reserved: &[Range<usize>],
+    ) -> (usize, usize) {
+        let start = place(out, 6 + 12 * self.fields.len(), reserved);
+        let next = start + 2 + 12 * self.fields.len();
+        // fields come from a parsed IFD, whose field count is a u16.
+        out.extend_from_slice(&order.u16_bytes(self.fields.len() as u16));
+        out.resize(next + 4, 0);
+        for (i, field) in self.fields.iter().enumerate() {
+            let entry = start + 2 + 12 * i;
+            out[entry..entry + 2].copy_from_slice(&order.u16_bytes(field.tag));
+            out[entry + 2..entry + 4].copy_from_slice(&order.u16_bytes(field.field_type));
+            out[entry + 4..entry + 8].copy_from_slice(&order.u32_bytes(field.count));
+            let offset = match &field.value {
+                Value::Bytes(bytes) if bytes.len() <= 4 => {
+                    out[entry + 8..entry + 8 + bytes.len()].copy_from_slice(bytes);
+                    continue;
+                }
+                Value::Bytes(bytes) | Value::Thumbnail(bytes) => {
+                    let offset = place(out, bytes.len(), reserved);
+                    out.extend_from_slice(bytes);
+                    offset
+                }
+                // copied into its reserved range once everything else is written.
+                Value::MakerNote { offset, .. } => *offset,
+                Value::Ifd(sub) => sub.write(order, out, reserved).0,
+            };
+            // EXIF data fits in a single APP1 segment, so offsets always fit.
+            out[entry + 8..entry + 12].copy_from_slice(&order.u32_bytes(offset as u32));
+        }
+        (start, next)
+    }
+}
+/// Pads `out` so that `length` bytes can be appended at an even offset without overlapping a `reserved` range.
+/// Returns that offset.
+fn place(out: &mut Vec<u8>, length: usize, reserved: &[Range<usize>]) -> usize {
+    loop {
+        // offsets must be even.
+        if out.len() % 2 == 1 {
+            out.push(0);
+        }
+        let (start, end) = (out.len(), out.len() + length);
+        match reserved
+            .iter()
+            .find(|range| range.start < end && start < range.end)
+        {
+            Some(range) => out.resize(range.end, 0),
+            None => return start,
+        }
+    }
+}
+/// Parsed EXIF data, which can be filtered and written back.
+#[derive(Debug, Clone)]
+pub(crate) struct Exif {
+    order: ByteOrder,
+    /// The chain of IFDs: IFD0 for the image, IFD1 for its thumbnail.
+    ifds: Vec<Ifd>,
+}
+impl Exif {
+    /// Parses EXIF data. Returns `None` if it is malformed.
+    pub(crate) fn parse(tiff: &[u8]) -> Option<Self> {
+        let (order, mut offset) = header(tiff)?;
+        let mut ifds = Vec::new();
+        while offset != 0 && ifds.len() < MAX_CHAIN {
+            let (ifd, next) = Ifd::parse(tiff, order, offset, 0)?;
+            ifds.push(ifd);
+            offset = next;
+        }
+        Some(Self { order, ifds })
+    }
+    /// Filters fields of every IFD according to `retain`, which is given the tag of each field.
+    /// Returns whether any field is left.
+    pub(crate) fn retain(&mut self, retain: impl Fn(u16) -> Retain) -> bool {
+        let mut any = false;
+        for ifd in self.ifds.iter_mut() {
+            any |= ifd.retain(&retain);
+        }
+        // IFD0 is mandatory, but empty IFDs at the end of the chain are not.
+        while self.ifds.len() > 1 && self.ifds.last().is_some_and(|ifd| ifd.fields.is_empty()) {
+            self.ifds.pop();
+        }
+        any
+    }
+    /// Reads an ASCII field of IFD0 or of the EXIF IFD.
+    pub(crate) fn ascii(&self, tag: u16) -> Option<String> {
+        let ifd0 = self.ifds.first()?;
+        let field = ifd0
+            .field(tag)
+            .or_else(|| ifd0.sub(tag::EXIF_IFD)?.field(tag))?;
+        let (ASCII, Value::Bytes(bytes)) = (field.field_type, &field.value) else {
+            return None;
+        };
+        let text = bytes.split(|&byte| byte == 0).next().unwrap_or_default();
+        let text = String::from_utf8_lossy(text).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+    /// Reads the GPS coordinates, as a latitude and a longitude in decimal degrees.
+    pub(crate) fn gps(&self) -> Option<(f64, f64)> {
+        let gps = self.ifds.first()?.sub(tag::GPS_INFO)?;
+        // GPSLatitudeRef, GPSLatitude, GPSLongitudeRef and GPSLongitude.
+        let coordinate = |reference: u16, degrees: u16, negative: u8| {
+            let sign = match &gps.field(reference)?.value {
+                Value::Bytes(bytes) if bytes.first() == Some(&negative) => -1.0,
+                _ => 1.0,
+            };
+            let field = gps.field(degrees)?;
+            let (RATIONAL, 3, Value::Bytes(bytes)) = (field.field_type, field.count, &field.value)
+            else {
+                return None;
+            };
+            // degrees, minutes and seconds.
+            let mut dms = bytes.chunks_exact(8).map(|rational| {
+                let numerator = self.order.u32(rational, 0).unwrap_or_default();
+                let denominator = self.order.u32(rational, 4).unwrap_or_default();
+                if denominator == 0 {
+                    0.0
+                } else {
+                    f64::from(numerator) / f64::from(denominator)
+                }
+            });
+            let (d, m, s) = (dms.next()?, dms.next()?, dms.next()?);
+            Some(sign * (d + m / 60.0 + s / 3600.0))
+        };
+        Some((coordinate(1, 2, b'S')?, coordinate(3, 4, b'W')?))
+    }
+    /// Writes the EXIF data back into TIFF bytes.
+    /// Values are relocated, except maker notes, which are kept at their original offset.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut notes = Vec::new();
+        for ifd in self.ifds.iter() {
+            ifd.maker_notes(&mut notes);
+        }
+        let reserved: Vec<Range<usize>> = notes.iter().map(|(range, _)| range.clone()).collect();
+        let mut out = match self.order {
+            ByteOrder::Little => b"II".to_vec(),
+            ByteOrder::Big => b"MM".to_vec(),
+        };
+        out.extend_from_slice(&self.order.u16_bytes(42));
+        out.extend_from_slice(&[0; 4]);
+        let mut previous = 4;
+        for ifd in self.ifds.iter() {
+            let (start, next) = ifd.write(self.order, &mut out, &reserved);
+            out[previous..previous + 4].copy_from_slice(&self.order.u32_bytes(start as u32));
+            previous = next;
+        }
+        for (range, bytes) in notes {
+            if out.len() < range.end {
+                out.resize(range.end, 0);
+            }
+            out[range].copy_from_slice(bytes);
+        }
+        out
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{by_address::ByAddress, error};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+/// Images waiting to be compressed, along with their index, or the error raised while pulling them.
+pub(crate) type Queue = Box<dyn Iterator<Item = (usize, Result<Vec<u8>, error::Error>)> + Send>;
+/// Images pulled lazily from an iterator, shared by the threads compressing them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Lazy(ByAddress<Mutex<Box<dyn Iterator<Item = Vec<u8>> + Send>>>);
+impl Lazy {
+    pub(crate) fn new(iter: impl Iterator<Item = Vec<u8>> + Send + 'static) -> Self {
+        Self(ByAddress(Arc::new(Mutex::new(Box::new(iter)))))
+    }
+}
+/// Images to compress in parallel.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Input {
+    /// Images held in memory up front, along with their index.
+    Vec(VecDeque<(usize, Vec<u8>)>),
+    /// Images pulled one at a time by each thread, so that only the images being compressed are held in memory.
+    Lazy(Lazy),
+}
+impl Default for Input {
+    fn default() -> Self {
+        Self::Vec(VecDeque::new())
+    }
+}
+impl Input {
+    /// Returns how many images there are, unless they are pulled lazily.
+    pub(crate) fn len(&self) -> Option<usize> {
+        match self {
+            Input::Vec(vec) => Some(vec.len()),
+            Input::Lazy(_) => None,
+        }
+    }
+    /// Turns the images into a queue the threads take them from, along with their index.
+    ///
+    /// A panic while pulling from lazy input is returned as [`error::Error::InputPanicked`] in place of the image,
+    /// and ends the queue: the iterator cannot be trusted to go on after that.
+    pub(crate) fn into_queue(self) -> Queue {
+        match self {
+            Input::Vec(vec) => Box::new(vec.into_iter().map(|(index, bytes)| (index, Ok(bytes)))),
+            Input::Lazy(lazy) => {
+                let mut next_index = 0;
+                let mut panicked = false;
+                let pull = std::iter::from_fn(move || {
+                    if panicked {
+                        return None;
+                    }
+                    let mut iter = lazy.0.lock().ok()?;
+                    let index = next_index;
+                    match panic::catch_unwind(AssertUnwindSafe(|| iter.next())) {
+                        Ok(bytes) => {
+                            next_index += 1;
+                            Some((index, Ok(bytes?)))
+                        }
+                        Err(_) => {
+                            panicked = true;
+                            Some((index, Err(error::Error::InputPanicked { index })))
+                        }
+                    }
+                });
+                Box::new(pull)
+            }
+        }
+    }
+}
+impl Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Input::Vec(vec) => write!(f, "{}", vec.len()),
+            Input::Lazy(_) => write!(f, "unknown (lazy)"),
+        }
+    }
+}
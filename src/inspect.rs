@@ -0,0 +1,294 @@
+use crate::{
+    error,
+    exif::{self, tag, Exif},
+    jpeg, quality, Subsampling,
+};
+use bytes::Bytes;
+use img_parts::{jpeg::Jpeg, ImageEXIF, ImageICC};
+use std::fmt::Display;
+
+/// An APP segment of a JPEG, as listed by [`Metadata::app_segments`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AppSegment {
+    number: u8,
+    identifier: String,
+    size: usize,
+}
+impl AppSegment {
+    /// Returns the number n of this APPn segment, from 0 to 15.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+    /// Returns the identifier the segment starts with, e.g. `JFIF`, `Exif`, `ICC_PROFILE` or `http://ns.adobe.com/xap/1.0/` (XMP).
+    /// Empty if the segment starts without one.
+    pub fn identifier(&self) -> &str {
+        self.identifier.as_str()
+    }
+    /// Returns the size of the contents of the segment in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+impl Display for AppSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "APP{} {:?} ({} bytes)",
+            self.number, self.identifier, self.size
+        )
+    }
+}
+/// Metadata of a JPEG, read without decoding the image.
+/// Use it to inspect a JPEG before deciding how to compress it.
+/// # Example
+/// ```
+/// use jippigy::Metadata;
+/// use image::{RgbImage, ImageFormat::Jpeg};
+/// use std::io::Cursor;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut bytes = Vec::new();
+///     let img = RgbImage::new(1000, 1000);
+///     let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg)?;
+///     let metadata = Metadata::from_bytes(bytes.as_slice())?;
+///     assert_eq!(metadata.dimensions(), (1000, 1000));
+///     println!("{metadata}");
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    width: u32,
+    height: u32,
+    components: u8,
+    subsampling: Option<Subsampling>,
+    quality: Option<u8>,
+    progressive: bool,
+    orientation: Option<u16>,
+    date_time: Option<String>,
+    make: Option<String>,
+    model: Option<String>,
+    gps: Option<(f64, f64)>,
+    icc_description: Option<String>,
+    app_segments: Vec<AppSegment>,
+}
+impl Metadata {
+    /// Reads the metadata of JPEG `bytes`.
+    ///
+    /// Fails if `bytes` is not a JPEG or has no frame header. Malformed EXIF data or ICC profiles are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Metadata, error::Error> {
+        Self::from_shared(Bytes::copy_from_slice(bytes))
+    }
+    /// Reads the metadata of JPEG `bytes` like [`from_bytes`](Metadata::from_bytes) does, without copying them.
+    pub(crate) fn from_shared(bytes: Bytes) -> Result<Metadata, error::Error> {
+        let parts = match Jpeg::from_bytes(bytes) {
+            Err(e) => return Err(error::Error::ImgPartError(e.to_string())),
+            Ok(res) => res,
+        };
+        let Some(frame) = parts
+            .segments()
+            .iter()
+            .find(|segment| jpeg::is_frame(segment.marker()))
+        else {
+            return Err(error::Error::JpegError(
+                "No frame header found.".to_string(),
+            ));
+        };
+        let [_precision, h0, h1, w0, w1, count, rest @ ..] = frame.contents().as_ref() else {
+            return Err(error::Error::JpegError(
+                "JPEG data is corrupted: Invalid frame header.".to_string(),
+            ));
+        };
+        // component id, sampling factors and quantization table id of each component.
+        let components: Vec<&[u8]> = rest.chunks_exact(3).take(usize::from(*count)).collect();
+        let tiff = parts.exif();
+        let parsed = tiff.as_deref().and_then(Exif::parse);
+        let app_segments = parts
+            .segments()
+            .iter()
+            .filter(|segment| matches!(segment.marker(), jpeg::APP0..=jpeg::APP15))
+            .map(|segment| AppSegment {
+                number: segment.marker() - jpeg::APP0,
+                identifier: identifier(segment.contents()),
+                size: segment.contents().len(),
+            })
+            .collect();
+        Ok(Metadata {
+            width: u32::from(u16::from_be_bytes([*w0, *w1])),
+            height: u32::from(u16::from_be_bytes([*h0, *h1])),
+            components: *count,
+            subsampling: subsampling(&components),
+            quality: estimate_quality(&parts, &components),
+            progressive: matches!(
+                frame.marker(),
+                jpeg::SOF2 | jpeg::SOF6 | jpeg::SOF10 | jpeg::SOF14
+            ),
+            orientation: tiff.as_deref().and_then(exif::orientation),
+            date_time: parsed.as_ref().and_then(|exif| {
+                exif.ascii(tag::DATE_TIME_ORIGINAL)
+                    .or_else(|| exif.ascii(tag::DATE_TIME))
+            }),
+            make: parsed.as_ref().and_then(|exif| exif.ascii(tag::MAKE)),
+            model: parsed.as_ref().and_then(|exif| exif.ascii(tag::MODEL)),
+            gps: parsed.as_ref().and_then(Exif::gps),
+            icc_description: parts.icc_profile().as_deref().and_then(icc_description),
+            app_segments,
+        })
+    }
+    /// Returns the width and height of the image in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    /// Returns the number of color components: 1 for grayscale, 3 for YCbCr (or RGB), 4 for CMYK.
+    pub fn components(&self) -> u8 {
+        self.components
+    }
+    /// Returns the chroma subsampling of the image, or `None` if it is not one of [`Subsampling`]'s.
+    /// Never [`Subsampling::Auto`].
+    pub fn subsampling(&self) -> Option<Subsampling> {
+        self.subsampling
+    }
+    /// Returns the quality (from 1 to 100) the image was most likely compressed with, estimated from its quantization tables.
+    /// The estimate is exact for JPEGs compressed with libjpeg (or turbojpeg) and most encoders based on it,
+    /// and an approximation of the equivalent libjpeg quality for other encoders.
+    pub fn quality(&self) -> Option<u8> {
+        self.quality
+    }
+    /// Returns whether the image is a progressive JPEG.
+    pub fn is_progressive(&self) -> bool {
+        self.progressive
+    }
+    /// Returns the EXIF Orientation tag, from 1 (upright) to 8.
+    pub fn orientation(&self) -> Option<u16> {
+        self.orientation
+    }
+    /// Returns the EXIF date and time the image was captured (or else last modified), e.g. `2024:01:01 10:00:00`.
+    pub fn date_time(&self) -> Option<&str> {
+        self.date_time.as_deref()
+    }
+    /// Returns the EXIF manufacturer of the camera.
+    pub fn make(&self) -> Option<&str> {
+        self.make.as_deref()
+    }
+    /// Returns the EXIF model of the camera.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+    /// Returns the EXIF GPS coordinates the image was captured at, as a latitude and a longitude in decimal degrees.
+    /// Southern latitudes and western longitudes are negative.
+    pub fn gps(&self) -> Option<(f64, f64)> {
+        self.gps
+    }
+    /// Returns the description of the ICC profile, e.g. `sRGB IEC61966-2.1`.
+    pub fn icc_description(&self) -> Option<&str> {
+        self.icc_description.as_deref()
+    }
+    /// Returns the APP segments of the image, in order.
+    pub fn app_segments(&self) -> &[AppSegment] {
+        self.app_segments.as_slice()
+    }
+}
+impl Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        write!(
+            f,
+            "dimensions: {}x{}\ncomponents: {}\nsubsampling: {}\nquality: {}\nprogressive: {}\norientation: {}\ndate and time: {}\ncamera: {} {}\nGPS: {}\nICC profile: {}\nAPP segments: {}",
+            self.width,
+            self.height,
+            self.components,
+            show(self.subsampling.map(|subsampling| subsampling.to_string())),
+            show(self.quality.map(|quality| quality.to_string())),
+            self.progressive,
+            show(self.orientation.map(|orientation| orientation.to_string())),
+            show(self.date_time.clone()),
+            show(self.make.clone()),
+            show(self.model.clone()),
+            show(self.gps.map(|(latitude, longitude)| format!("{latitude:.6}, {longitude:.6}"))),
+            show(self.icc_description.clone()),
+            self.app_segments.len()
+        )
+    }
+}
+/// Maps the sampling factors of the components to a subsampling.
+fn subsampling(components: &[&[u8]]) -> Option<Subsampling> {
+    let factors = |component: &[u8]| (component[1] >> 4, component[1] & 0x0F);
+    match components {
+        [_] => Some(Subsampling::Gray),
+        [y, cb, cr] if factors(cb) == (1, 1) && factors(cr) == (1, 1) => match factors(y) {
+            (1, 1) => Some(Subsampling::S444),
+            (2, 1) => Some(Subsampling::S422),
+            (2, 2) => Some(Subsampling::S420),
+            (1, 2) => Some(Subsampling::S440),
+            (4, 1) => Some(Subsampling::S411),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+/// Estimates the quality from the quantization tables of the first (luminance) and second (chrominance) components.
+fn estimate_quality(parts: &Jpeg, components: &[&[u8]]) -> Option<u8> {
+    let tables: Vec<(u8, quality::Table)> = parts
+        .segments_by_marker(jpeg::DQT)
+        .flat_map(|segment| quality::parse_tables(segment.contents()))
+        .collect();
+    // tables can be redefined, the last definition is used.
+    let table = |id: u8| tables.iter().rev().find(|(table, _)| *table == id);
+    let luminance = components.first()?[2];
+    let chrominance = components
+        .get(1)
+        .map(|component| component[2])
+        .filter(|&id| id != luminance);
+    Some(quality::estimate(
+        &table(luminance)?.1,
+        chrominance.and_then(table).map(|(_, table)| table),
+    ))
+}
+/// Reads the identifier at the start of a segment: printable ASCII up to a NUL byte.
+fn identifier(contents: &[u8]) -> String {
+    let end = contents
+        .iter()
+        .position(|&byte| !(byte.is_ascii_graphic() || byte == b' '))
+        .unwrap_or(contents.len());
+    if contents.get(end).is_some_and(|&byte| byte != 0) {
+        return String::new();
+    }
+    String::from_utf8_lossy(&contents[..end]).to_string()
+}
+/// Reads the description ('desc' tag) of an ICC profile.
+fn icc_description(profile: &[u8]) -> Option<String> {
+    let u32_at = |bytes: &[u8], pos: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?) as usize)
+    };
+    // the tag table follows the 128 bytes long header.
+    let count = u32_at(profile, 128)?;
+    let entry = (0..count)
+        .map(|i| 132 + 12 * i)
+        .take_while(|&entry| entry + 12 <= profile.len())
+        .find(|&entry| profile.get(entry..entry + 4) == Some(b"desc"))?;
+    let offset = u32_at(profile, entry + 4)?;
+    let size = u32_at(profile, entry + 8)?;
+    let desc = profile.get(offset..offset.checked_add(size)?)?;
+    let text = match desc.get(..4)? {
+        // ICC v2 textDescriptionType, with an ASCII description.
+        b"desc" => {
+            let length = u32_at(desc, 8)?;
+            let ascii = desc.get(12..12usize.checked_add(length)?)?;
+            let ascii = ascii.split(|&byte| byte == 0).next().unwrap_or_default();
+            String::from_utf8_lossy(ascii).to_string()
+        }
+        // ICC v4 multiLocalizedUnicodeType, whose first record is used.
+        b"mluc" => {
+            let length = u32_at(desc, 20)?;
+            let start = u32_at(desc, 24)?;
+            let utf16 = desc.get(start..start.checked_add(length)?)?;
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
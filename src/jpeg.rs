@@ -0,0 +1,89 @@
+use crate::error;
+
+/// Start of frame, baseline DCT.
+pub(crate) const SOF0: u8 = 0xC0;
+/// Start of frame, extended sequential DCT.
+pub(crate) const SOF1: u8 = 0xC1;
+/// Start of frame, progressive DCT.
+pub(crate) const SOF2: u8 = 0xC2;
+/// Start of frame, differential progressive DCT (Huffman coding).
+pub(crate) const SOF6: u8 = 0xC6;
+/// Start of frame, progressive DCT (arithmetic coding).
+pub(crate) const SOF10: u8 = 0xCA;
+/// Start of frame, differential progressive DCT (arithmetic coding).
+pub(crate) const SOF14: u8 = 0xCE;
+/// Define Huffman table(s).
+pub(crate) const DHT: u8 = 0xC4;
+/// Start of image.
+pub(crate) const SOI: u8 = 0xD8;
+/// End of image.
+pub(crate) const EOI: u8 = 0xD9;
+/// Start of scan.
+pub(crate) const SOS: u8 = 0xDA;
+/// Define quantization table(s).
+pub(crate) const DQT: u8 = 0xDB;
+/// Application segment 0, JFIF.
+pub(crate) const APP0: u8 = 0xE0;
+/// Application segment 1, EXIF data or XMP.
+pub(crate) const APP1: u8 = 0xE1;
+/// Application segment 2, ICC profiles or MPF.
+pub(crate) const APP2: u8 = 0xE2;
+/// Application segment 13, Photoshop image resources.
+pub(crate) const APP13: u8 = 0xED;
+/// Application segment 14, Adobe.
+pub(crate) const APP14: u8 = 0xEE;
+/// Application segment 15.
+pub(crate) const APP15: u8 = 0xEF;
+/// Comment.
+pub(crate) const COM: u8 = 0xFE;
+
+/// Whether the marker is a standalone marker, without a length or contents.
+fn is_standalone(marker: u8) -> bool {
+    matches!(marker, 0x01 | 0xD0..=0xD7 | SOI | EOI)
+}
+/// Whether the marker starts a frame (SOF0 to SOF15, except DHT, JPG and DAC).
+pub(crate) fn is_frame(marker: u8) -> bool {
+    matches!(marker, 0xC0..=0xCF) && !matches!(marker, DHT | 0xC8 | 0xCC)
+}
+/// Finds the marker of the frame header of JPEG bytes, e.g. [`SOF2`] for a progressive JPEG.
+/// The frame header always comes before the first scan, so entropy-coded data is never walked through.
+pub(crate) fn frame_marker(bytes: &[u8]) -> Result<u8, error::Error> {
+    if !bytes.starts_with(&[0xFF, SOI]) {
+        return Err(error::Error::JpegError(
+            "Not a JPEG (missing SOI marker).".to_string(),
+        ));
+    }
+    let mut pos = 2;
+    loop {
+        // skip fill bytes before the marker.
+        while bytes.get(pos) == Some(&0xFF) && bytes.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let (Some(0xFF), Some(&marker)) = (bytes.get(pos), bytes.get(pos + 1)) else {
+            return Err(truncated());
+        };
+        pos += 2;
+        if is_frame(marker) {
+            return Ok(marker);
+        }
+        if marker == SOS || marker == EOI {
+            return Err(error::Error::JpegError(
+                "No frame header found.".to_string(),
+            ));
+        }
+        if is_standalone(marker) {
+            continue;
+        }
+        let length = match bytes.get(pos..pos + 2) {
+            Some(length) => usize::from(u16::from_be_bytes([length[0], length[1]])),
+            None => return Err(truncated()),
+        };
+        if length < 2 {
+            return Err(truncated());
+        }
+        pos += length;
+    }
+}
+fn truncated() -> error::Error {
+    error::Error::JpegError("JPEG data is truncated or corrupted.".to_string())
+}
@@ -1,12 +1,28 @@
 #![warn(missing_docs)]
 //! A simple, multi-threaded JPEG compression crate, powered by [turbojpeg](https://github.com/honzasp/rust-turbojpeg).
 //!
-//! Uses the common 2x2 chroma subsampling for compression.
+//! Uses the common 2x2 chroma subsampling for compression by default. Other chroma subsamplings can be chosen with [`Subsampling`], including keeping the subsampling of the original JPEG.
 //!
-//! Currently this crate doesn't give you finer controls over how you compress your JPEGs. Check out [turbojpeg](https://github.com/honzasp/rust-turbojpeg) for more options.
+//! Currently this crate doesn't give you much finer controls over how you compress your JPEGs. Check out [turbojpeg](https://github.com/honzasp/rust-turbojpeg) for more options.
+//!
+//! JPEGs can also be recompressed losslessly with [`Lossless`], which only rewrites their entropy coding.
 //!
 //! Provides methods of compressing JPEG images in a single-threaded  or multi-threaded way. Both methods preserves [EXIF](https://en.wikipedia.org/wiki/Exif) data of the original JPEG through [img_parts](https://docs.rs/img-parts/latest/img_parts/) crate.
 //!
+//! Which EXIF data is preserved can be chosen with [`MetadataPolicy`], e.g. to strip GPS coordinates. XMP, IPTC, comments and other ancillary segments are preserved as well, see [`Ancillary`].
+//!
+//! The metadata of a JPEG (dimensions, subsampling, estimated quality, EXIF data, etc.) can be inspected beforehand with [`Metadata`].
+//!
+//! Nothing is printed: problems that do not prevent compression (e.g. a missing ICC profile) are reported as [`Warning`]s by [`CompressionOutput::warnings`].
+//! Enable the `log` feature to also log them through the [log](https://docs.rs/log) crate.
+//!
+//! Enable the `tracing` feature to instrument compression with [tracing](https://docs.rs/tracing) spans: `compress` for each image (with its index and sizes),
+//! and `decode`, `encode` and `preserve` (EXIF data, ICC profiles and ancillary segments) within it, as well as `queue_wait` for each thread waiting for its next image. Without the feature, none of it is compiled.
+//!
+//! Enable the `rayon` feature to compress images with [`Parallel`] on the threads of an existing [rayon](https://docs.rs/rayon) pool, see [`ParallelBuilder::with_pool`].
+//!
+//! Enable the `async` feature to compress images from [tokio](https://docs.rs/tokio) tasks without blocking them, with `Single::compress_async` and `Parallel::into_stream`.
+//!
 //! # Error building `turbojpeg`?
 //! The problem is typically related to `turbojpeg-sys` (see this [question](https://github.com/rfdzan/smoljpg/issues/4#issuecomment-2036065574) and my [attempt](https://github.com/rfdzan/jippigy/actions/runs/8552014019/job/23432251063#step:3:327) at setting up CI for this crate).
 //!
@@ -17,7 +33,7 @@
 
 //! ## Single image compressions with [`Single`]
 //!```
-//! use jippigy::Single;
+//! use jippigy::{Single, Subsampling};
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! # use image::{RgbImage, ImageFormat::Jpeg};
 //! # use std::io::Cursor;
@@ -26,8 +42,32 @@
 //! # let _write = img.write_to(&mut Cursor::new(&mut vec), Jpeg)?;
 //!     let _result: Vec<u8> = Single::from_bytes(vec)
 //!         .with_quality(80)
+//!         .with_subsampling(Subsampling::S444)
+//!         .build()
+//!         .compress()?;
+//!     Ok(())
+//! }
+//!```
+//!
+//! ## Compressing a directory with [`Directory`]
+//! [`Directory`] compresses every JPEG file of a directory (and optionally its subdirectories) into another directory, with the same names.
+//!```
+//! use jippigy::Directory;
+//! use tempdir::TempDir;
+//! # const TEST_DIR: &str = "./tests/images/";
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     // this temporary directory is here for doctest purposes,
+//!     // but you will create your own directory.
+//!     let tempdir = TempDir::new("compressed")?;
+//!     let reports = Directory::from_paths(TEST_DIR, tempdir.path())
+//!         .with_recursive(true)
+//!         .with_parallel(|parallel| parallel.with_quality(50).with_device(4))
 //!         .build()
 //!         .compress()?;
+//!     for report in reports {
+//!         println!("{report}");
+//!     }
+//!     tempdir.close()?;
 //!     Ok(())
 //! }
 //!```
@@ -94,16 +134,59 @@
 //! [`turbojpeg`]: https://github.com/honzasp/rust-turbojpeg
 //! [`turbojpeg-sys`]: https://github.com/honzasp/rust-turbojpeg/tree/master/turbojpeg-sys
 //! [`Building`]: https://github.com/honzasp/rust-turbojpeg/tree/master/turbojpeg-sys#building
+mod abort;
+mod ancillary;
+mod backend;
 mod bulk;
+mod by_address;
 mod compress;
 mod defaults;
+mod directory;
 mod error;
+mod exif;
+mod float;
+mod input;
+mod inspect;
+mod jpeg;
+mod lossless;
+mod metadata;
+mod ordering;
+mod orientation;
+mod output;
+mod progress;
+mod quality;
+mod resize;
 mod single;
+mod ssim;
+#[cfg(feature = "async")]
+mod stream;
+mod subsampling;
+mod warning;
 
-pub(crate) use self::compress::Compress;
-pub(crate) use self::defaults::{DEVICE, QUALITY};
+pub(crate) use self::compress::{Compress, Margin, Mode, Settings};
+pub(crate) use self::defaults::{BUFFER, DEVICE, QUALITY};
+pub(crate) use self::orientation::Orientation;
+pub(crate) use self::resize::Resize;
+#[cfg(feature = "async")]
+pub use self::stream::ParallelStream;
 pub use self::{
-    bulk::{Parallel, ParallelBuilder, ParallelIntoIterator},
+    abort::AbortHandle,
+    ancillary::Ancillary,
+    bulk::{
+        Parallel, ParallelBuilder, ParallelIndexedIterator, ParallelIntoIterator,
+        ParallelOutputIterator,
+    },
+    directory::{Directory, DirectoryBuilder, FileReport},
     error::Error,
+    exif::tag,
+    inspect::{AppSegment, Metadata},
+    lossless::Lossless,
+    metadata::MetadataPolicy,
+    ordering::Ordering,
+    output::{CompressionOutput, Outcome},
+    progress::Progress,
+    resize::Filter,
     single::{Single, SingleBuilder},
+    subsampling::Subsampling,
+    warning::Warning,
 };
@@ -0,0 +1,51 @@
+use crate::{error, jpeg};
+use std::fmt::Display;
+use turbojpeg::Transform;
+
+/// Lossless recompression of a JPEG. Only the entropy coding is rewritten,
+/// DCT coefficients are kept bit-exact (as [jpegtran](https://libjpeg-turbo.org/) does) and the image is never decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum Lossless {
+    /// Rewrites the Huffman tables to be optimal for the image.
+    /// Baseline JPEGs stay baseline, progressive JPEGs stay progressive.
+    #[default]
+    OptimizeHuffman,
+    /// Rewrites the image as a progressive JPEG, which also uses optimal Huffman tables.
+    Progressive,
+}
+impl Lossless {
+    /// Recompresses JPEG `bytes` losslessly.
+    /// APP segments other than JFIF (APP0) and Adobe (APP14), and comments are dropped.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "encode", skip_all, fields(size = bytes.len()))
+    )]
+    pub(crate) fn recompress(&self, bytes: &[u8]) -> Result<Vec<u8>, error::Error> {
+        let frame = jpeg::frame_marker(bytes)?;
+        let mut transform = Transform::default();
+        transform.optimize = true;
+        transform.copy_none = true;
+        transform.progressive = match (self, frame) {
+            (Lossless::OptimizeHuffman, jpeg::SOF0 | jpeg::SOF1) => false,
+            (Lossless::OptimizeHuffman, jpeg::SOF2) | (Lossless::Progressive, _) => true,
+            (Lossless::OptimizeHuffman, _) => {
+                return Err(error::Error::JpegError(
+                    "Huffman optimization only supports Huffman-coded sequential and progressive JPEGs."
+                        .to_string(),
+                ))
+            }
+        };
+        match turbojpeg::transform(&transform, bytes) {
+            Err(e) => Err(error::Error::TurboJPEGError(e.to_string())),
+            Ok(res) => Ok(res.to_vec()),
+        }
+    }
+}
+impl Display for Lossless {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lossless::OptimizeHuffman => write!(f, "optimized Huffman tables"),
+            Lossless::Progressive => write!(f, "progressive"),
+        }
+    }
+}
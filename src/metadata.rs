@@ -0,0 +1,84 @@
+use crate::exif::{self, tag, Exif, Retain};
+use crate::Warning;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Which EXIF data of the original image is written into the compressed image.
+/// Tags are EXIF tag numbers, see [`tag`] for the common ones.
+///
+/// Tags pointing to a sub-IFD ([`tag::EXIF_IFD`], [`tag::GPS_INFO`] and [`tag::INTEROPERABILITY_IFD`])
+/// keep or strip the whole sub-IFD when they are listed.
+/// Tags of the thumbnail (IFD1) are kept or stripped like any other tag.
+/// When EXIF data is filtered, its values are moved around, except the maker note ([`tag::MAKER_NOTE`]),
+/// which stays at its original offset because offsets within it may be relative to the EXIF data.
+///
+/// Any policy but [`MetadataPolicy::KeepAll`] also drops the ancillary segments that can hold the same data in another form,
+/// whatever [`Ancillary`](crate::Ancillary) segments are preserved: XMP, IPTC and other APP segments (e.g. GPS coordinates or serial numbers in XMP).
+///
+/// Defaults to [`MetadataPolicy::KeepAll`]. ICC profiles are always kept.
+/// # Example
+/// ```
+/// use jippigy::{tag, MetadataPolicy};
+/// let _policy = MetadataPolicy::Keep([tag::DATE_TIME_ORIGINAL, tag::COPYRIGHT].into());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum MetadataPolicy {
+    /// Keeps the EXIF data as is.
+    #[default]
+    KeepAll,
+    /// Strips the EXIF data entirely.
+    StripAll,
+    /// Keeps the listed tags only.
+    Keep(Arc<[u16]>),
+    /// Strips the listed tags only.
+    Strip(Arc<[u16]>),
+    /// Strips the GPS IFD, which holds the location the image was captured at.
+    StripGps,
+}
+impl MetadataPolicy {
+    /// Whether the policy strips any EXIF data.
+    pub(crate) fn strips(&self) -> bool {
+        *self != MetadataPolicy::KeepAll
+    }
+    /// Decides what to do with a field of EXIF data.
+    fn retain(&self, field: u16) -> Retain {
+        match self {
+            MetadataPolicy::KeepAll => Retain::Keep,
+            MetadataPolicy::StripAll => Retain::Remove,
+            MetadataPolicy::Keep(tags) if tags.contains(&field) => Retain::Keep,
+            MetadataPolicy::Keep(_) if exif::is_pointer(field) => Retain::Filter,
+            MetadataPolicy::Keep(_) => Retain::Remove,
+            MetadataPolicy::Strip(tags) if tags.contains(&field) => Retain::Remove,
+            MetadataPolicy::StripGps if field == tag::GPS_INFO => Retain::Remove,
+            MetadataPolicy::Strip(_) | MetadataPolicy::StripGps => Retain::Filter,
+        }
+    }
+    /// Applies the policy to EXIF data (a TIFF structure), returning `None` if no EXIF data is left.
+    /// Fails if the EXIF data has to be filtered but is malformed.
+    pub(crate) fn apply(&self, tiff: Vec<u8>) -> Result<Option<Vec<u8>>, Warning> {
+        match self {
+            MetadataPolicy::KeepAll => Ok(Some(tiff)),
+            MetadataPolicy::StripAll => Ok(None),
+            _ if tiff.is_empty() => Ok(None),
+            _ => {
+                let Some(mut exif) = Exif::parse(tiff.as_slice()) else {
+                    return Err(Warning::MalformedExif);
+                };
+                Ok(exif
+                    .retain(|field| self.retain(field))
+                    .then(|| exif.to_bytes()))
+            }
+        }
+    }
+}
+impl Display for MetadataPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataPolicy::KeepAll => write!(f, "keep all"),
+            MetadataPolicy::StripAll => write!(f, "strip all"),
+            MetadataPolicy::Keep(tags) => write!(f, "keep {tags:#06x?}"),
+            MetadataPolicy::Strip(tags) => write!(f, "strip {tags:#06x?}"),
+            MetadataPolicy::StripGps => write!(f, "strip GPS"),
+        }
+    }
+}
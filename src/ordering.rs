@@ -0,0 +1,24 @@
+use std::fmt::Display;
+
+/// The order in which [`Parallel`](crate::Parallel) returns compressed images.
+///
+/// Defaults to [`Ordering::InOrder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum Ordering {
+    /// In the same order the images were passed in.
+    /// An image that takes long to compress holds back the images after it, while threads keep compressing the next ones,
+    /// up to as many images ahead of it as there are threads and room in the buffer.
+    #[default]
+    InOrder,
+    /// As soon as each image is compressed, whatever its position.
+    /// Use [`Parallel::into_indexed_iter`](crate::Parallel::into_indexed_iter) to know which image each result belongs to.
+    AsCompleted,
+}
+impl Display for Ordering {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ordering::InOrder => write!(f, "in order"),
+            Ordering::AsCompleted => write!(f, "as completed"),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{error, exif};
+use image::{imageops, RgbImage};
+use img_parts::{jpeg::Jpeg, ImageEXIF};
+
+/// How the pixels of an image must be transformed to be displayed upright, as given by the EXIF Orientation tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Orientation {
+    /// 1: already upright.
+    #[default]
+    Normal,
+    /// 2: mirrored horizontally.
+    FlipHorizontal,
+    /// 3: rotated by 180°.
+    Rotate180,
+    /// 4: mirrored vertically.
+    FlipVertical,
+    /// 5: mirrored along the top-left to bottom-right diagonal.
+    Transpose,
+    /// 6: must be rotated 90° clockwise.
+    Rotate90,
+    /// 7: mirrored along the top-right to bottom-left diagonal.
+    Transverse,
+    /// 8: must be rotated 270° clockwise.
+    Rotate270,
+}
+impl Orientation {
+    /// Reads the orientation from the EXIF data of JPEG `bytes`.
+    /// JPEGs without EXIF data or without a valid Orientation tag are [`Orientation::Normal`].
+    pub(crate) fn read(bytes: &[u8]) -> Result<Self, error::Error> {
+        let jpeg = match Jpeg::from_bytes(bytes.to_vec().into()) {
+            Err(e) => return Err(error::Error::ImgPartError(e.to_string())),
+            Ok(res) => res,
+        };
+        let orientation = jpeg
+            .exif()
+            .and_then(|tiff| exif::orientation(&tiff))
+            .unwrap_or(1);
+        Ok(Self::from(orientation))
+    }
+    /// Whether the width and height of the image are swapped once it is upright.
+    pub(crate) fn is_transposed(&self) -> bool {
+        matches!(
+            self,
+            Orientation::Transpose
+                | Orientation::Rotate90
+                | Orientation::Transverse
+                | Orientation::Rotate270
+        )
+    }
+    /// Transforms the pixels of `image` so that it is upright.
+    pub(crate) fn apply(&self, image: RgbImage) -> RgbImage {
+        match self {
+            Orientation::Normal => image,
+            Orientation::FlipHorizontal => imageops::flip_horizontal(&image),
+            Orientation::Rotate180 => imageops::rotate180(&image),
+            Orientation::FlipVertical => imageops::flip_vertical(&image),
+            Orientation::Transpose => imageops::flip_horizontal(&imageops::rotate90(&image)),
+            Orientation::Rotate90 => imageops::rotate90(&image),
+            Orientation::Transverse => imageops::flip_horizontal(&imageops::rotate270(&image)),
+            Orientation::Rotate270 => imageops::rotate270(&image),
+        }
+    }
+}
+impl From<u16> for Orientation {
+    fn from(value: u16) -> Self {
+        match value {
+            2 => Orientation::FlipHorizontal,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipVertical,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => Orientation::Normal,
+        }
+    }
+}
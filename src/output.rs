@@ -0,0 +1,151 @@
+use crate::Warning;
+use bytes::Bytes;
+use img_parts::{jpeg::Jpeg, ImageEXIF, ImageICC};
+use std::fmt::Display;
+use std::time::Duration;
+
+/// What happened to an image, as reported by [`CompressionOutput::outcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum Outcome {
+    /// The image has been compressed.
+    #[default]
+    Compressed,
+    /// The original image has been kept, because its estimated quality is lower than the requested quality.
+    SourceQualityLower,
+    /// The original image has been kept, because the compressed image is not smaller (by the margin of the size guard, if any).
+    NotSmaller,
+}
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Compressed => write!(f, "compressed"),
+            Outcome::SourceQualityLower => write!(f, "kept, source quality is lower"),
+            Outcome::NotSmaller => write!(f, "kept, compressed image is not smaller"),
+        }
+    }
+}
+/// Compressed JPEG bytes, along with details on the original image and how it was compressed.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct CompressionOutput {
+    bytes: Vec<u8>,
+    quality: Option<u8>,
+    similarity: Option<f64>,
+    outcome: Outcome,
+    index: usize,
+    original_size: usize,
+    has_exif: bool,
+    has_icc: bool,
+    duration: Duration,
+    warnings: Vec<Warning>,
+}
+impl CompressionOutput {
+    /// Creates a new output from compressed bytes and the quality they were compressed with.
+    pub(crate) fn new(bytes: Vec<u8>, quality: Option<u8>) -> Self {
+        Self {
+            bytes,
+            quality,
+            similarity: None,
+            outcome: Outcome::Compressed,
+            index: 0,
+            original_size: 0,
+            has_exif: false,
+            has_icc: false,
+            duration: Duration::ZERO,
+            warnings: Vec::new(),
+        }
+    }
+    /// Creates a new output from the original bytes, kept as they are.
+    pub(crate) fn kept(bytes: Vec<u8>, outcome: Outcome) -> Self {
+        Self {
+            bytes,
+            quality: None,
+            similarity: None,
+            outcome,
+            index: 0,
+            original_size: 0,
+            has_exif: false,
+            has_icc: false,
+            duration: Duration::ZERO,
+            warnings: Vec::new(),
+        }
+    }
+    /// Records the similarity score reached by the compressed image.
+    pub(crate) fn with_similarity(self, similarity: f64) -> Self {
+        Self {
+            similarity: Some(similarity),
+            ..self
+        }
+    }
+    /// Records the warnings raised while compressing the image.
+    pub(crate) fn with_warnings(self, warnings: Vec<Warning>) -> Self {
+        Self { warnings, ..self }
+    }
+    /// Records the size of the original image and whether it has EXIF data and an ICC profile.
+    pub(crate) fn with_original(self, original: &Bytes) -> Self {
+        let original_size = original.len();
+        // segments of the parsed image share the original bytes, nothing is copied.
+        let parts = Jpeg::from_bytes(original.clone()).ok();
+        Self {
+            original_size,
+            has_exif: parts.as_ref().is_some_and(|parts| parts.exif().is_some()),
+            has_icc: parts.is_some_and(|parts| parts.icc_profile().is_some()),
+            ..self
+        }
+    }
+    /// Records the time the compression took.
+    pub(crate) fn with_duration(self, duration: Duration) -> Self {
+        Self { duration, ..self }
+    }
+    /// Records the index of the original image among the images passed in.
+    pub(crate) fn with_index(self, index: usize) -> Self {
+        Self { index, ..self }
+    }
+    /// Returns the compressed JPEG bytes, or the original bytes if they have been kept (see [`outcome`](CompressionOutput::outcome)).
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+    /// Consumes the output, returning the compressed JPEG bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+    /// Returns the quality used for compression.
+    /// When compressing towards a target, this is the quality chosen by the search.
+    /// Lossless recompressions do not use a quality and return `None`, as do original images that have been kept.
+    pub fn quality(&self) -> Option<u8> {
+        self.quality
+    }
+    /// Returns the similarity between the original and the compressed image, from 0.0 to 1.0.
+    /// Only measured when compressing with a minimum similarity, otherwise returns `None`.
+    pub fn similarity(&self) -> Option<f64> {
+        self.similarity
+    }
+    /// Returns whether the image has been compressed, or why the original image has been kept instead.
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+    /// Returns the index of the original image among the images passed in, e.g. to [`Parallel::from_vec`](crate::Parallel::from_vec).
+    /// Always 0 for a [`Single`](crate::Single) image.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// Returns the size of the original image in bytes.
+    pub fn original_size(&self) -> usize {
+        self.original_size
+    }
+    /// Returns whether the original image has EXIF data, whatever the [`MetadataPolicy`](crate::MetadataPolicy).
+    pub fn has_exif(&self) -> bool {
+        self.has_exif
+    }
+    /// Returns whether the original image has an ICC profile.
+    pub fn has_icc(&self) -> bool {
+        self.has_icc
+    }
+    /// Returns the time it took to compress the image (or to decide to keep it), searches included.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+    /// Returns the problems that did not prevent the image from being compressed, e.g. a missing ICC profile.
+    pub fn warnings(&self) -> &[Warning] {
+        self.warnings.as_slice()
+    }
+}
@@ -0,0 +1,135 @@
+use crate::by_address::ByAddress;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How far back [`Progress::throughput`] looks.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(3);
+
+/// How far a compression task has gone, as reported to the callbacks given to
+/// [`ParallelBuilder::with_progress`](crate::ParallelBuilder::with_progress) and [`SingleBuilder::with_progress`](crate::SingleBuilder::with_progress).
+///
+/// With [`Parallel`](crate::Parallel), progress is counted in images. With [`Single`](crate::Single), it is counted in encoding passes
+/// of [`with_target_size`](crate::SingleBuilder::with_target_size) and [`with_min_similarity`](crate::SingleBuilder::with_min_similarity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Progress {
+    completed: usize,
+    total: Option<usize>,
+    bytes_in: u64,
+    bytes_out: u64,
+    elapsed: Duration,
+    /// Original bytes compressed within the last [`THROUGHPUT_WINDOW`].
+    recent_bytes_in: u64,
+}
+impl Progress {
+    /// Returns how many images (or passes) are done, including failed ones.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+    /// Returns how many images there are, or at most how many passes there can be.
+    /// Images pulled lazily with [`Parallel::from_iter`](crate::Parallel::from_iter) cannot be counted, so it is `None` then.
+    pub fn total(&self) -> Option<usize> {
+        self.total
+    }
+    /// Returns the size in bytes of the original images compressed so far (the original image, once for each pass).
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
+    /// Returns the size in bytes of the compressed images so far (of every pass).
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
+    /// Returns the time elapsed since the compression started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    /// Returns the current throughput, in bytes of original images compressed per second over the last 3 seconds.
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.min(THROUGHPUT_WINDOW).as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.recent_bytes_in as f64 / seconds
+    }
+}
+impl Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self
+            .total
+            .map_or_else(|| "?".to_string(), |total| total.to_string());
+        write!(
+            f,
+            "{}/{} done, {} bytes in, {} bytes out, {:.0} bytes/s",
+            self.completed,
+            total,
+            self.bytes_in,
+            self.bytes_out,
+            self.throughput()
+        )
+    }
+}
+/// A progress callback provided by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Callback(ByAddress<dyn Fn(Progress) + Send + Sync>);
+impl Callback {
+    pub(crate) fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(ByAddress(Arc::new(callback)))
+    }
+}
+/// What has been done so far.
+#[derive(Debug, Default)]
+struct Counts {
+    completed: usize,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// When each unit of work within the last [`THROUGHPUT_WINDOW`] was done, and its original bytes.
+    recent: VecDeque<(Instant, u64)>,
+}
+/// Counts what has been done so far and reports it to a callback.
+#[derive(Debug)]
+pub(crate) struct Tracker {
+    callback: Callback,
+    start: Instant,
+    counts: Mutex<Counts>,
+    total: Option<usize>,
+}
+impl Tracker {
+    pub(crate) fn new(callback: Callback, total: Option<usize>) -> Self {
+        Self {
+            callback,
+            start: Instant::now(),
+            counts: Mutex::new(Counts::default()),
+            total,
+        }
+    }
+    /// Records a unit of work done, whether it succeeded or not, and reports the progress so far.
+    pub(crate) fn record(&self, bytes_in: usize, bytes_out: usize) {
+        // the lock is held while reporting, so that progress is reported in order.
+        let Ok(mut counts) = self.counts.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        counts.completed += 1;
+        counts.bytes_in += bytes_in as u64;
+        counts.bytes_out += bytes_out as u64;
+        counts.recent.push_back((now, bytes_in as u64));
+        while let Some(&(done, _)) = counts.recent.front() {
+            if now.duration_since(done) <= THROUGHPUT_WINDOW {
+                break;
+            }
+            counts.recent.pop_front();
+        }
+        let progress = Progress {
+            completed: counts.completed,
+            total: self.total,
+            bytes_in: counts.bytes_in,
+            bytes_out: counts.bytes_out,
+            elapsed: now.duration_since(self.start),
+            recent_bytes_in: counts.recent.iter().map(|(_, bytes)| bytes).sum(),
+        };
+        // a panicking callback only misses this report, it neither poisons the counts nor fails the compression.
+        let _report = panic::catch_unwind(AssertUnwindSafe(|| (self.callback.0)(progress)));
+    }
+}
@@ -0,0 +1,90 @@
+/// Luminance quantization table of the JPEG standard (Annex K), which libjpeg scales by quality. In natural order.
+const LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99, //
+];
+/// Chrominance quantization table of the JPEG standard (Annex K). In natural order.
+const CHROMINANCE: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+];
+/// Natural position of each coefficient, in the zigzag order of DQT segments.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// A quantization table, in the zigzag order of DQT segments.
+pub(crate) type Table = [u16; 64];
+
+/// Reads the quantization tables of a DQT segment, along with their ids.
+pub(crate) fn parse_tables(mut contents: &[u8]) -> Vec<(u8, Table)> {
+    let mut tables = Vec::new();
+    while let [info, rest @ ..] = contents {
+        let sixteen_bits = info >> 4 == 1;
+        let length = if sixteen_bits { 128 } else { 64 };
+        let Some(values) = rest.get(..length) else {
+            break;
+        };
+        let mut table = [0; 64];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = if sixteen_bits {
+                u16::from_be_bytes([values[2 * i], values[2 * i + 1]])
+            } else {
+                u16::from(values[i])
+            };
+        }
+        tables.push((info & 0x0F, table));
+        contents = &rest[length..];
+    }
+    tables
+}
+/// Scales a table of the JPEG standard to `quality` the way libjpeg does.
+fn scaled(standard: &[u16; 64], quality: u8) -> impl Iterator<Item = u16> + '_ {
+    let quality = u32::from(quality);
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+    ZIGZAG.iter().map(move |&natural| {
+        let value = (u32::from(standard[natural]) * scale + 50) / 100;
+        value.clamp(1, 255) as u16
+    })
+}
+/// Estimates the quality (from 1 to 100) a JPEG was compressed with, from its luminance and chrominance quantization tables.
+///
+/// The estimate is the libjpeg quality whose tables are the closest, so it is exact for JPEGs compressed with libjpeg
+/// (and most encoders based on it), and an approximation for other encoders.
+pub(crate) fn estimate(luminance: &Table, chrominance: Option<&Table>) -> u8 {
+    let distance = |quality: u8| {
+        let mut distance: u32 = scaled(&LUMINANCE, quality)
+            .zip(luminance.iter())
+            .map(|(expected, &actual)| u32::from(expected.abs_diff(actual)))
+            .sum();
+        if let Some(chrominance) = chrominance {
+            distance += scaled(&CHROMINANCE, quality)
+                .zip(chrominance.iter())
+                .map(|(expected, &actual)| u32::from(expected.abs_diff(actual)))
+                .sum::<u32>();
+        }
+        distance
+    };
+    // the lowest quality wins ties.
+    (1..=100)
+        .min_by_key(|&quality| distance(quality))
+        .unwrap_or(100)
+}
@@ -0,0 +1,175 @@
+use crate::{error, float::Float};
+use image::{imageops::FilterType, RgbImage};
+use std::fmt::Display;
+use turbojpeg::{decompress_image, Decompressor, Image, PixelFormat, ScalingFactor};
+
+/// Resampling filter used when resizing images.
+///
+/// Defaults to [`Filter::Lanczos3`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum Filter {
+    /// Nearest neighbor. Fastest, but blocky.
+    Nearest,
+    /// Linear (triangle) filter.
+    Triangle,
+    /// Cubic (Catmull-Rom) filter.
+    CatmullRom,
+    /// Gaussian filter.
+    Gaussian,
+    /// Lanczos filter with a window of 3. Slowest, but sharpest.
+    #[default]
+    Lanczos3,
+}
+impl Filter {
+    fn filter_type(&self) -> FilterType {
+        match self {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_show = match self {
+            Filter::Nearest => "nearest",
+            Filter::Triangle => "triangle",
+            Filter::CatmullRom => "catmull-rom",
+            Filter::Gaussian => "gaussian",
+            Filter::Lanczos3 => "lanczos3",
+        };
+        write!(f, "{to_show}")
+    }
+}
+/// A downscaling factor between 0.0 (exclusive) and 1.0 (inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Scale(Float);
+impl Scale {
+    fn val(&self) -> f64 {
+        self.0 .0
+    }
+}
+impl From<f64> for Scale {
+    fn from(value: f64) -> Self {
+        if value.is_nan() || value > 1.0 {
+            return Self(Float(1.0));
+        }
+        Self(Float(value.max(f64::MIN_POSITIVE)))
+    }
+}
+/// How images are resized before being re-encoded. Images are never upscaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub(crate) enum Resize {
+    /// Keep the original dimensions.
+    #[default]
+    Original,
+    /// Fit within a width and a height, keeping the aspect ratio.
+    MaxDimensions(u32, u32),
+    /// Multiply both dimensions by a factor.
+    Scale(Scale),
+}
+impl Resize {
+    /// Swaps the maximum width and height, for images whose width and height are swapped after being resized.
+    pub(crate) fn transposed(&self) -> Resize {
+        match self {
+            Resize::MaxDimensions(width, height) => Resize::MaxDimensions(*height, *width),
+            _ => *self,
+        }
+    }
+    /// Computes the dimensions an image of `width` by `height` is resized to,
+    /// or `None` if it keeps its dimensions.
+    fn target(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let factor = match self {
+            Resize::Original => return None,
+            Resize::MaxDimensions(max_width, max_height) => f64::min(
+                f64::from(*max_width) / f64::from(width),
+                f64::from(*max_height) / f64::from(height),
+            ),
+            Resize::Scale(scale) => scale.val(),
+        };
+        if factor >= 1.0 {
+            return None;
+        }
+        let scaled = |dimension: u32| ((f64::from(dimension) * factor).round() as u32).max(1);
+        Some((scaled(width), scaled(height)))
+    }
+    /// Decodes JPEG `bytes` and resizes it.
+    ///
+    /// The image is first decoded with the smallest DCT scaling factor of turbojpeg (from 1/8 to 8/8)
+    /// whose dimensions are still at least the target dimensions, which is much faster than decoding it whole.
+    /// Whatever is left is resized with `filter`.
+    pub(crate) fn decode(&self, bytes: &[u8], filter: Filter) -> Result<RgbImage, error::Error> {
+        let header = match turbojpeg::read_header(bytes) {
+            Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
+            Ok(res) => res,
+        };
+        let (width, height) = (header.width as u32, header.height as u32);
+        let Some((target_width, target_height)) = self.target(width, height) else {
+            return match decompress_image(bytes) {
+                Err(e) => Err(error::Error::TurboJPEGError(e.to_string())),
+                Ok(res) => Ok(res),
+            };
+        };
+        let eighths = (1..=8)
+            .find(|eighths| {
+                (width * eighths).div_ceil(8) >= target_width
+                    && (height * eighths).div_ceil(8) >= target_height
+            })
+            .unwrap_or(8);
+        let mut decompressor = match Decompressor::new() {
+            Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
+            Ok(res) => res,
+        };
+        let divisor = gcd(eighths, 8);
+        let scaling_factor =
+            ScalingFactor::new((eighths / divisor) as usize, (8 / divisor) as usize);
+        if let Err(e) = decompressor.set_scaling_factor(scaling_factor) {
+            return Err(error::Error::TurboJPEGError(e.to_string()));
+        }
+        let scaled = header.scaled(scaling_factor);
+        let mut pixels = vec![0; 3 * scaled.width * scaled.height];
+        let image = Image {
+            pixels: pixels.as_mut_slice(),
+            width: scaled.width,
+            pitch: 3 * scaled.width,
+            height: scaled.height,
+            format: PixelFormat::RGB,
+        };
+        if let Err(e) = decompressor.decompress(bytes, image) {
+            return Err(error::Error::TurboJPEGError(e.to_string()));
+        }
+        let Some(image) = RgbImage::from_raw(scaled.width as u32, scaled.height as u32, pixels)
+        else {
+            return Err(error::Error::JippigyInternalError(
+                "BUG: decoded image does not match its dimensions.".to_string(),
+            ));
+        };
+        if image.dimensions() == (target_width, target_height) {
+            return Ok(image);
+        }
+        Ok(image::imageops::resize(
+            &image,
+            target_width,
+            target_height,
+            filter.filter_type(),
+        ))
+    }
+}
+impl Display for Resize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resize::Original => write!(f, "original dimensions"),
+            Resize::MaxDimensions(width, height) => write!(f, "fit within {width}x{height}"),
+            Resize::Scale(scale) => write!(f, "scale by {:.3}", scale.val()),
+        }
+    }
+}
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
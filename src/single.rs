@@ -1,13 +1,18 @@
 use std::fmt::Display;
+use std::sync::Arc;
 
-use crate::{error, Compress, QUALITY};
+use crate::{
+    error, progress::Callback, resize, ssim, Ancillary, Compress, CompressionOutput, Filter,
+    Lossless, Margin, MetadataPolicy, Mode, Progress, Resize, Settings, Subsampling,
+};
 /// Custom configuration for building a [`Single`].
 /// This struct is not meant to be used directly.
 /// Use [`Single::from_bytes`] instead.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
 pub struct SingleBuilder {
     bytes_slice: Vec<u8>,
-    quality: u8,
+    settings: Settings,
+    progress: Option<Callback>,
 }
 impl SingleBuilder {
     /// Builds a new Single with custom configurations.
@@ -17,13 +22,14 @@ impl SingleBuilder {
     /// use jippigy::Single;
     /// fn main() {
     ///     let bytes: Vec<u8> = Vec::new();
-    ///     let _build = Single::from_bytes(bytes).build();          
+    ///     let _build = Single::from_bytes(bytes).build();
     /// }
     /// ```
     pub fn build(self) -> Single {
         Single {
             bytes_slice: self.bytes_slice,
-            quality: self.quality,
+            settings: self.settings,
+            progress: self.progress,
         }
     }
     /// Specifies the quality of compressed images.
@@ -32,55 +38,280 @@ impl SingleBuilder {
     /// **This method is optional**.
     pub fn with_quality(self, quality: u8) -> SingleBuilder {
         SingleBuilder {
-            bytes_slice: self.bytes_slice,
-            quality,
+            settings: Settings {
+                quality,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies the chroma subsampling of compressed images.
+    /// Defaults to [`Subsampling::S420`] (2x2 chroma subsampling).
+    /// Use [`Subsampling::Auto`] to keep the subsampling of the original image.
+    ///
+    /// **This method is optional**.
+    pub fn with_subsampling(self, subsampling: Subsampling) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                subsampling,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether the compressed image are encoded as progressive JPEGs, which are displayed in increasing detail as they load.
+    /// Defaults to `false` (baseline JPEGs).
+    /// Lossless recompressions use [`Lossless::Progressive`] instead.
+    ///
+    /// **This method is optional**.
+    pub fn with_progressive(self, progressive: bool) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                progressive,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Downscales the image to fit within `width` by `height` before compressing, keeping its aspect ratio.
+    /// Images that already fit are not resized, and images are never upscaled.
+    /// Lossless recompressions are not resized.
+    ///
+    /// **This method is optional**.
+    pub fn with_max_dimensions(self, width: u32, height: u32) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                resize: Resize::MaxDimensions(width, height),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Downscales the image by `factor` before compressing, e.g. `0.5` halves its width and height.
+    /// The factor is clamped between 0.0 (exclusive) and 1.0 (inclusive), so images are never upscaled.
+    /// Lossless recompressions are not resized.
+    ///
+    /// **This method is optional**.
+    pub fn with_scale(self, factor: f64) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                resize: Resize::Scale(resize::Scale::from(factor)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies the resampling filter used when resizing with [`with_max_dimensions`](SingleBuilder::with_max_dimensions) or [`with_scale`](SingleBuilder::with_scale).
+    /// Defaults to [`Filter::Lanczos3`].
+    ///
+    /// Images are first shrunk while decoding with turbojpeg's DCT scaling (by a factor of n/8),
+    /// the filter only resizes what remains.
+    ///
+    /// **This method is optional**.
+    pub fn with_filter(self, filter: Filter) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                filter,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether the image is rotated (or flipped) upright according to its EXIF Orientation tag before compressing.
+    /// The Orientation tag of the preserved EXIF data is then reset to 1 (upright), so that viewers do not rotate it again.
+    /// Defaults to `false` (pixels and EXIF data are kept as they are).
+    /// Lossless recompressions are not rotated.
+    ///
+    /// **This method is optional**.
+    pub fn with_auto_orient(self, auto_orient: bool) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                auto_orient,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies which EXIF data of the original image is preserved, e.g. to strip GPS coordinates and serial numbers.
+    /// Defaults to [`MetadataPolicy::KeepAll`]. See [`MetadataPolicy`] for the available policies.
+    ///
+    /// **This method is optional**.
+    pub fn with_metadata(self, metadata: MetadataPolicy) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                metadata,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies which ancillary segments of the original image (XMP, IPTC, comments, etc.) are preserved, besides EXIF data and ICC profiles.
+    /// Defaults to [`Ancillary::ALL`]. Pass an empty list to preserve none of them.
+    ///
+    /// **This method is optional**.
+    pub fn with_ancillary(self, ancillary: impl Into<Arc<[Ancillary]>>) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                ancillary: ancillary.into(),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Specifies whether the original image is kept as is when compressing it would make it worse or larger:
+    /// - if its quality, estimated from its quantization tables, is lower than the quality given by [`with_quality`](SingleBuilder::with_quality),
+    ///   re-encoding it would only add artifacts. When compressing towards a target, the search is capped at its quality instead.
+    /// - if the compressed image is not smaller than the original one, unless a margin is given with [`with_size_guard`](SingleBuilder::with_size_guard).
+    ///
+    /// Defaults to `false`. Use [`CompressionOutput::outcome`](crate::CompressionOutput::outcome) to know whether the original image has been kept.
+    ///
+    /// **This method is optional**.
+    pub fn with_skip_if_lower_quality(self, skip: bool) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                skip_lower_quality: skip,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Keeps the original image as is unless its compressed image is smaller by at least `margin`, a fraction of the original size
+    /// from 0.0 to 1.0. For example, `0.05` requires compressing to save at least 5% of the original size,
+    /// and `0.0` only requires the compressed image to be smaller.
+    ///
+    /// Without it, compressed images are returned even if they are larger than the original ones, as often happens with already optimized images.
+    /// Use [`CompressionOutput::outcome`](crate::CompressionOutput::outcome) to know whether the original image has been kept.
+    ///
+    /// **This method is optional**.
+    pub fn with_size_guard(self, margin: f64) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                size_guard: Some(Margin::from(margin)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Compresses to a file size of at most `bytes`, EXIF data included, instead of using a fixed quality.
+    /// The highest quality that fits is searched for, up to the quality given by [`with_quality`](SingleBuilder::with_quality).
+    /// Use [`Single::compress_output`] to know which quality was chosen.
+    ///
+    /// Compression fails with [`Error::TargetError`](crate::Error::TargetError) if even the lowest quality does not fit.
+    ///
+    /// **This method is optional**.
+    pub fn with_target_size(self, bytes: usize) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                mode: Mode::TargetSize(bytes),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Compresses as far as possible while keeping the compressed image at least `similarity` similar to the original,
+    /// instead of using a fixed quality. Similarity is measured with [SSIM](https://en.wikipedia.org/wiki/Structural_similarity)
+    /// on the decoded images, from 0.0 (unrelated) to 1.0 (identical), and is clamped to this range.
+    /// The lowest quality that is similar enough is searched for, up to the quality given by [`with_quality`](SingleBuilder::with_quality).
+    /// Use [`Single::compress_output`] to know which quality was chosen and the similarity it reached.
+    ///
+    /// Compression fails with [`Error::TargetError`](crate::Error::TargetError) if even the highest quality is not similar enough.
+    ///
+    /// **This method is optional**.
+    pub fn with_min_similarity(self, similarity: f64) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                mode: Mode::MinSimilarity(ssim::Score::from(similarity)),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Recompresses losslessly instead of re-encoding: only the entropy coding is rewritten
+    /// and the image is never decoded, so no quality is lost. See [`Lossless`] for the available methods.
+    /// Quality and subsampling are not used.
+    ///
+    /// **This method is optional**.
+    pub fn with_lossless(self, lossless: Lossless) -> SingleBuilder {
+        SingleBuilder {
+            settings: Settings {
+                mode: Mode::Lossless(lossless),
+                ..self.settings
+            },
+            ..self
+        }
+    }
+    /// Calls `progress` after each encoding pass of [`with_target_size`](SingleBuilder::with_target_size)
+    /// and [`with_min_similarity`](SingleBuilder::with_min_similarity), which may take several passes to find the right quality.
+    /// See [`Progress`] for what is reported. Other modes are not reported.
+    /// A panic in `progress` is caught and only skips that report.
+    ///
+    /// **This method is optional**.
+    /// # Example
+    /// ```
+    /// use jippigy::Single;
+    /// use image::{RgbImage, ImageFormat::Jpeg};
+    /// use std::io::Cursor;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>>{
+    ///     let mut bytes = Vec::new();
+    ///     let img = RgbImage::new(1000, 1000);
+    ///     let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg)?;
+    ///     let _result: Vec<u8> = Single::from_bytes(bytes)
+    ///         .with_target_size(200 * 1024)
+    ///         .with_progress(|progress| println!("pass {}: {progress}", progress.completed()))
+    ///         .build()
+    ///         .compress()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_progress(
+        self,
+        progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> SingleBuilder {
+        SingleBuilder {
+            progress: Some(Callback::new(progress)),
+            ..self
         }
     }
 }
 impl Display for SingleBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let to_show = self
-            .bytes_slice
-            .iter()
-            .take(8)
-            .map(|bytes| bytes)
-            .collect::<Vec<&u8>>();
-        write!(
-            f,
-            "bytes: {:#x?} (truncated)\nquality: {}",
-            to_show, self.quality
-        )
+        let to_show = self.bytes_slice.iter().take(8).collect::<Vec<&u8>>();
+        write!(f, "bytes: {:#x?} (truncated)\n{}", to_show, self.settings)
     }
 }
 /// Single image compressions.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
 pub struct Single {
     bytes_slice: Vec<u8>,
-    quality: u8,
+    settings: Settings,
+    progress: Option<Callback>,
 }
 impl Single {
     /// Creates a single image compression task from a given byte slice. Returns a [`SingleBuilder`].
     ///
     /// This method initializes the compression task with the following defaults:
     /// - Default final quality is 95% (95% of the original quality).
+    /// - Default chroma subsampling is 4:2:0 (2x2).
+    /// - Compressed images are baseline (not progressive) JPEGs.
     /// # Example
     /// ```
-    /// use jippigy::Single;     
+    /// use jippigy::Single;
     /// fn main() {
-    ///     let bytes: Vec<u8> = Vec::new();   
+    ///     let bytes: Vec<u8> = Vec::new();
     ///     let _single = Single::from_bytes(bytes);
     /// }
     /// ```
     pub fn from_bytes(bytes_slice: Vec<u8>) -> SingleBuilder {
         SingleBuilder {
             bytes_slice,
-            quality: QUALITY,
+            settings: Settings::default(),
+            progress: None,
         }
     }
     /// Compress a single image.
     /// # Example
     /// ```
-    /// use jippigy::Single;     
+    /// use jippigy::Single;
     /// use image::{RgbImage, ImageFormat::Jpeg};
     /// use std::io::Cursor;
     /// fn main() -> Result<(), Box<dyn std::error::Error>>{
@@ -95,22 +326,66 @@ impl Single {
     /// }
     /// ```
     pub fn compress(self) -> Result<Vec<u8>, error::Error> {
-        let compress = Compress::new(self.bytes_slice, self.quality).compress()?;
-        Ok(compress)
+        let compress = self.compress_output()?;
+        Ok(compress.into_bytes())
+    }
+    /// Compress a single image, returning the compressed bytes along with details on how it was compressed.
+    /// # Example
+    /// ```
+    /// use jippigy::Single;
+    /// use image::{RgbImage, ImageFormat::Jpeg};
+    /// use std::io::Cursor;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>>{
+    ///     let mut bytes = Vec::new();
+    ///     let img = RgbImage::new(1000, 1000);
+    ///     let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg)?;
+    ///     let output = Single::from_bytes(bytes)
+    ///         .with_target_size(200 * 1024)
+    ///         .build()
+    ///         .compress_output()?;
+    ///     assert!(output.bytes().len() <= 200 * 1024);
+    ///     println!("compressed with quality {:?}", output.quality());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn compress_output(self) -> Result<CompressionOutput, error::Error> {
+        Compress::new(self.bytes_slice, self.settings)
+            .with_progress(self.progress)
+            .compress()
+    }
+    /// Compress a single image like [`compress`](Single::compress) does, on tokio's blocking threads instead of blocking the async task.
+    /// Must be called from within a tokio runtime.
+    ///
+    /// Dropping the future before the compression starts stops it from being scheduled.
+    /// A compression that has already started runs to completion in the background, and its result is discarded.
+    ///
+    /// Requires the `async` feature.
+    /// # Example
+    /// ```
+    /// use jippigy::Single;
+    /// use image::{RgbImage, ImageFormat::Jpeg};
+    /// use std::io::Cursor;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>>{
+    ///     let mut bytes = Vec::new();
+    ///     let img = RgbImage::new(1000, 1000);
+    ///     let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg)?;
+    ///     let _result: Vec<u8> = Single::from_bytes(bytes)
+    ///         .with_quality(80)
+    ///         .build()
+    ///         .compress_async()
+    ///         .await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn compress_async(self) -> Result<Vec<u8>, error::Error> {
+        crate::stream::AbortOnDrop::spawn_blocking(move || self.compress()).await?
     }
 }
 impl Display for Single {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let to_show = self
-            .bytes_slice
-            .iter()
-            .take(8)
-            .map(|bytes| bytes)
-            .collect::<Vec<&u8>>();
-        write!(
-            f,
-            "bytes: {:#x?} (truncated)\nquality: {}",
-            to_show, self.quality
-        )
+        let to_show = self.bytes_slice.iter().take(8).collect::<Vec<&u8>>();
+        write!(f, "bytes: {:#x?} (truncated)\n{}", to_show, self.settings)
     }
 }
@@ -0,0 +1,111 @@
+use crate::float::Float;
+use image::RgbImage;
+use std::fmt::Display;
+
+/// Side length of the square windows SSIM is computed over.
+const WINDOW: u32 = 8;
+/// Distance between two neighboring windows.
+const STRIDE: u32 = 4;
+/// Stabilizes the luminance term, (0.01 * 255)^2.
+const C1: f64 = 6.5025;
+/// Stabilizes the contrast-structure term, (0.03 * 255)^2.
+const C2: f64 = 58.5225;
+
+/// A similarity score between 0.0 (unrelated) and 1.0 (identical).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Score(Float);
+impl Score {
+    pub(crate) fn val(&self) -> f64 {
+        self.0 .0
+    }
+}
+impl From<f64> for Score {
+    fn from(value: f64) -> Self {
+        if value.is_nan() {
+            return Self(Float(1.0));
+        }
+        Self(Float(value.clamp(0.0, 1.0)))
+    }
+}
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.4}", self.val())
+    }
+}
+/// The luma of an original image, computed once and compared against each of its compressions.
+pub(crate) struct Reference {
+    width: u32,
+    height: u32,
+    luma: Vec<f32>,
+}
+impl Reference {
+    pub(crate) fn new(original: &RgbImage) -> Self {
+        let (width, height) = original.dimensions();
+        Self {
+            width,
+            height,
+            luma: luma(original),
+        }
+    }
+    /// Computes the mean [SSIM](https://en.wikipedia.org/wiki/Structural_similarity) of the luma of the original image
+    /// and of `compressed`, which has the same dimensions.
+    /// Images smaller than a single window are compared as one window.
+    pub(crate) fn ssim(&self, compressed: &RgbImage) -> Score {
+        let (width, height) = (self.width, self.height);
+        let original = &self.luma;
+        let compressed = luma(compressed);
+        let window_width = std::cmp::min(WINDOW, width);
+        let window_height = std::cmp::min(WINDOW, height);
+        let mut total = 0.0;
+        let mut windows = 0usize;
+        let mut y = 0;
+        while y + window_height <= height {
+            let mut x = 0;
+            while x + window_width <= width {
+                let pixels = (y..y + window_height).flat_map(|row| {
+                    (x..x + window_width).map(move |col| (row * width + col) as usize)
+                });
+                total += window_ssim(pixels.map(|i| (original[i], compressed[i])));
+                windows += 1;
+                x += STRIDE;
+            }
+            y += STRIDE;
+        }
+        if windows == 0 {
+            return Score::from(1.0);
+        }
+        Score::from(total / windows as f64)
+    }
+}
+/// SSIM of a single window, from pairs of original and compressed luma values.
+fn window_ssim(pairs: impl Iterator<Item = (f32, f32)>) -> f64 {
+    let mut n = 0.0;
+    let (mut sum_a, mut sum_b) = (0.0, 0.0);
+    let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        // luma is stored as f32 to halve memory, sums need f64 to keep variances accurate.
+        let (a, b) = (f64::from(a), f64::from(b));
+        n += 1.0;
+        sum_a += a;
+        sum_b += b;
+        sum_aa += a * a;
+        sum_bb += b * b;
+        sum_ab += a * b;
+    }
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let var_a = sum_aa / n - mean_a * mean_a;
+    let var_b = sum_bb / n - mean_b * mean_b;
+    let covariance = sum_ab / n - mean_a * mean_b;
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+/// Converts an image into its luma (BT.601) values.
+fn luma(image: &RgbImage) -> Vec<f32> {
+    image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b] = pixel.0;
+            0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b)
+        })
+        .collect()
+}
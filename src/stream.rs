@@ -0,0 +1,69 @@
+use crate::{error, AbortHandle, ParallelIntoIterator};
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// A blocking task that is no longer scheduled once its future is dropped, if it has not started yet.
+/// turbojpeg cannot be interrupted, so a compression that has already started runs to completion and its result is discarded.
+pub(crate) struct AbortOnDrop<T>(JoinHandle<T>);
+impl<T: Send + 'static> AbortOnDrop<T> {
+    /// Runs `f` on tokio's blocking threads.
+    pub(crate) fn spawn_blocking(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self(tokio::task::spawn_blocking(f))
+    }
+}
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, error::Error>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map_err(|e| error::Error::JippigyInternalError(e.to_string()))
+    }
+}
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+/// Target type of [`Parallel::into_stream`](crate::Parallel::into_stream).
+///
+/// Dropping it stops scheduling further images. Images already being compressed run to completion and their results are discarded.
+///
+/// Requires the `async` feature.
+#[derive(Debug)]
+pub struct ParallelStream {
+    recv: mpsc::Receiver<Result<Vec<u8>, error::Error>>,
+    /// Stops the threads once the stream is dropped, even while the iterator is still waiting for its next item.
+    abort: AbortHandle,
+}
+impl ParallelStream {
+    /// Drives the iterator on tokio's blocking threads, handing its items over to the stream one at a time.
+    pub(crate) fn new(iter: ParallelIntoIterator) -> Self {
+        let abort = iter.abort_handle();
+        let (tx, recv) = mpsc::channel(1);
+        tokio::task::spawn_blocking(move || {
+            for result in iter {
+                // sending only fails once the stream has been dropped, dropping the iterator stops the threads.
+                if tx.blocking_send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { recv, abort }
+    }
+}
+impl Drop for ParallelStream {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
+impl Stream for ParallelStream {
+    /// A fallible containing compressed JPEG bytes.
+    type Item = Result<Vec<u8>, error::Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.recv.poll_recv(cx)
+    }
+}
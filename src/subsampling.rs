@@ -0,0 +1,58 @@
+use crate::error;
+use std::fmt::Display;
+use turbojpeg::Subsamp;
+
+/// Chroma subsampling used when re-encoding a JPEG.
+///
+/// Defaults to [`Subsampling::S420`] (the common 2x2 chroma subsampling).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum Subsampling {
+    /// 4:4:4, full chroma resolution. Keeps fine colored details (e.g. red text) sharp.
+    S444,
+    /// 4:2:2, chroma halved horizontally. Common for video frames.
+    S422,
+    /// 4:2:0, chroma halved both horizontally and vertically.
+    #[default]
+    S420,
+    /// 4:4:0, chroma halved vertically.
+    S440,
+    /// 4:1:1, chroma quartered horizontally.
+    S411,
+    /// Grayscale, chroma is discarded entirely.
+    Gray,
+    /// Reads the sampling factors of the source JPEG and reuses them.
+    Auto,
+}
+impl Subsampling {
+    /// Resolves this subsampling into turbojpeg's [`Subsamp`].
+    /// [`Subsampling::Auto`] reads the sampling factors from the header of `source`.
+    pub(crate) fn resolve(&self, source: &[u8]) -> Result<Subsamp, error::Error> {
+        let subsamp = match self {
+            Subsampling::S444 => Subsamp::None,
+            Subsampling::S422 => Subsamp::Sub2x1,
+            Subsampling::S420 => Subsamp::Sub2x2,
+            Subsampling::S440 => Subsamp::Sub1x2,
+            Subsampling::S411 => Subsamp::Sub4x1,
+            Subsampling::Gray => Subsamp::Gray,
+            Subsampling::Auto => match turbojpeg::read_header(source) {
+                Err(e) => return Err(error::Error::TurboJPEGError(e.to_string())),
+                Ok(header) => header.subsamp,
+            },
+        };
+        Ok(subsamp)
+    }
+}
+impl Display for Subsampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_show = match self {
+            Subsampling::S444 => "4:4:4",
+            Subsampling::S422 => "4:2:2",
+            Subsampling::S420 => "4:2:0",
+            Subsampling::S440 => "4:4:0",
+            Subsampling::S411 => "4:1:1",
+            Subsampling::Gray => "grayscale",
+            Subsampling::Auto => "auto (same as source)",
+        };
+        write!(f, "{to_show}")
+    }
+}
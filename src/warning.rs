@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+/// A problem that did not prevent an image from being compressed, as reported by [`CompressionOutput::warnings`](crate::CompressionOutput::warnings).
+///
+/// Warnings are never printed. With the `log` feature, they are also logged with [`log::warn!`](https://docs.rs/log/latest/log/macro.warn.html).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Warning {
+    /// The original image has no EXIF data to preserve.
+    NoExif,
+    /// The original image has no ICC profile to preserve.
+    NoIccProfile,
+    /// The EXIF data of the original image is malformed and could not be filtered by the [`MetadataPolicy`](crate::MetadataPolicy), so it has been stripped.
+    MalformedExif,
+}
+impl Warning {
+    /// Logs the warning, if the `log` feature is enabled.
+    pub(crate) fn log(&self) {
+        #[cfg(feature = "log")]
+        log::warn!("{self}");
+    }
+}
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::NoExif => write!(f, "No EXIF data found"),
+            Warning::NoIccProfile => write!(f, "No ICC profile found"),
+            Warning::MalformedExif => write!(f, "Malformed EXIF data has been stripped"),
+        }
+    }
+}
@@ -1,5 +1,12 @@
 use image::{ImageFormat::Jpeg, RgbImage};
-use jippigy::{Parallel, Single};
+use img_parts::{
+    jpeg::{Jpeg as JpegParts, JpegSegment},
+    ImageEXIF, ImageICC,
+};
+use jippigy::{
+    tag, Ancillary, Directory, Error, Filter, Lossless, Metadata, MetadataPolicy, Ordering,
+    Outcome, Parallel, Single, Subsampling, Warning,
+};
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::thread;
@@ -15,6 +22,172 @@ impl Dummy {
         let _write = img.write_to(&mut Cursor::new(&mut jpeg), Jpeg).unwrap();
         jpeg
     }
+    fn create_jpeg_image_with_metadata() -> Vec<u8> {
+        let mut jpeg = JpegParts::from_bytes(Self::create_jpeg_image().into()).unwrap();
+        jpeg.set_exif(Some(Self::exif().into()));
+        jpeg.set_icc_profile(Some(Self::icc_profile().into()));
+        jpeg.encoder().bytes().to_vec()
+    }
+    fn exif() -> Vec<u8> {
+        // little-endian TIFF header followed by an empty IFD.
+        b"II*\0\x08\0\0\0\0\0\0\0\0\0".to_vec()
+    }
+    fn create_rotated_jpeg_image() -> Vec<u8> {
+        // 40x20, red on its left half, displayed rotated 90° clockwise.
+        let img = RgbImage::from_fn(40, 20, |x, _| {
+            if x < 20 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        });
+        let mut bytes = Vec::new();
+        let _write = img.write_to(&mut Cursor::new(&mut bytes), Jpeg).unwrap();
+        let mut jpeg = JpegParts::from_bytes(bytes.into()).unwrap();
+        // little-endian TIFF header followed by an IFD with Orientation = 6.
+        let exif = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0\x06\0\0\0\0\0\0\0";
+        jpeg.set_exif(Some(exif.to_vec().into()));
+        jpeg.encoder().bytes().to_vec()
+    }
+    fn orientation(jpeg: &[u8]) -> u16 {
+        let exif = JpegParts::from_bytes(jpeg.to_vec().into())
+            .unwrap()
+            .exif()
+            .unwrap();
+        u16::from_le_bytes([exif[18], exif[19]])
+    }
+    fn create_jpeg_image_with_tags() -> Vec<u8> {
+        let mut jpeg = JpegParts::from_bytes(Self::create_jpeg_image().into()).unwrap();
+        jpeg.set_exif(Some(Self::exif_with_tags().into()));
+        jpeg.set_icc_profile(Some(Self::icc_profile().into()));
+        jpeg.encoder().bytes().to_vec()
+    }
+    fn exif_with_tags() -> Vec<u8> {
+        fn entry(tiff: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: [u8; 4]) {
+            tiff.extend_from_slice(&tag.to_be_bytes());
+            tiff.extend_from_slice(&field_type.to_be_bytes());
+            tiff.extend_from_slice(&count.to_be_bytes());
+            tiff.extend_from_slice(&value);
+        }
+        // big-endian TIFF header, IFD0 at 8 with its values at 62.
+        let mut tiff = b"MM\0*\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&4u16.to_be_bytes());
+        entry(&mut tiff, tag::MAKE, 2, 6, 62u32.to_be_bytes());
+        entry(&mut tiff, tag::COPYRIGHT, 2, 12, 68u32.to_be_bytes());
+        entry(&mut tiff, tag::EXIF_IFD, 4, 1, 80u32.to_be_bytes());
+        entry(&mut tiff, tag::GPS_INFO, 4, 1, 162u32.to_be_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(b"Canon\0(c) someone\0");
+        // EXIF IFD at 80, its values at 122.
+        tiff.extend_from_slice(&3u16.to_be_bytes());
+        entry(
+            &mut tiff,
+            tag::DATE_TIME_ORIGINAL,
+            2,
+            20,
+            122u32.to_be_bytes(),
+        );
+        entry(&mut tiff, tag::MAKER_NOTE, 7, 10, 142u32.to_be_bytes());
+        entry(
+            &mut tiff,
+            tag::BODY_SERIAL_NUMBER,
+            2,
+            9,
+            152u32.to_be_bytes(),
+        );
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(b"2024:01:01 10:00:00\0MAKERNOTE!SN123456\0\0");
+        // GPS IFD at 162, its values at 192.
+        tiff.extend_from_slice(&2u16.to_be_bytes());
+        entry(&mut tiff, 0x0000, 1, 4, [2, 3, 0, 0]);
+        entry(&mut tiff, 0x0012, 2, 7, 192u32.to_be_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(b"WGS-84\0");
+        tiff
+    }
+    fn exif_contains(jpeg: &[u8], needle: &[u8]) -> bool {
+        let exif = JpegParts::from_bytes(jpeg.to_vec().into())
+            .unwrap()
+            .exif()
+            .unwrap();
+        exif.windows(needle.len()).any(|window| window == needle)
+    }
+    fn exif_position(jpeg: &[u8], needle: &[u8]) -> Option<usize> {
+        let exif = JpegParts::from_bytes(jpeg.to_vec().into())
+            .unwrap()
+            .exif()
+            .unwrap();
+        exif.windows(needle.len())
+            .position(|window| window == needle)
+    }
+    fn create_jpeg_image_with_ancillary() -> Vec<u8> {
+        let mut jpeg = JpegParts::from_bytes(Self::create_jpeg_image().into()).unwrap();
+        for (marker, contents) in Self::ancillary() {
+            let segment = JpegSegment::new_with_contents(marker, contents.into());
+            jpeg.segments_mut().insert(1, segment);
+        }
+        jpeg.encoder().bytes().to_vec()
+    }
+    fn ancillary() -> Vec<(u8, Vec<u8>)> {
+        vec![
+            (0xE1, b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>".to_vec()),
+            (0xED, b"Photoshop 3.0\08BIM\x04\x04\0\0\0\0\0\0".to_vec()),
+            // Adobe APP14 of an RGB image (no color transform).
+            (0xEE, b"Adobe\0\x64\0\0\0\0\0".to_vec()),
+            (0xFE, b"a comment".to_vec()),
+            (0xE3, b"vendor data".to_vec()),
+        ]
+    }
+    fn segments(jpeg: &[u8], marker: u8) -> Vec<Vec<u8>> {
+        JpegParts::from_bytes(jpeg.to_vec().into())
+            .unwrap()
+            .segments_by_marker(marker)
+            .map(|segment| segment.contents().to_vec())
+            // compressed images always have an EXIF segment.
+            .filter(|contents| !contents.starts_with(b"Exif\0\0"))
+            .collect()
+    }
+    fn exif_with_gps() -> Vec<u8> {
+        fn entry(tiff: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: [u8; 4]) {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&field_type.to_le_bytes());
+            tiff.extend_from_slice(&count.to_le_bytes());
+            tiff.extend_from_slice(&value);
+        }
+        // little-endian TIFF header, IFD0 at 8 with Orientation = 3.
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        entry(&mut tiff, tag::ORIENTATION, 3, 1, [3, 0, 0, 0]);
+        entry(&mut tiff, tag::GPS_INFO, 4, 1, 38u32.to_le_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        // GPS IFD at 38 with 48°51'30" N, 2°17'40" W, its rationals at 92.
+        tiff.extend_from_slice(&4u16.to_le_bytes());
+        entry(&mut tiff, 1, 2, 2, *b"N\0\0\0");
+        entry(&mut tiff, 2, 5, 3, 92u32.to_le_bytes());
+        entry(&mut tiff, 3, 2, 2, *b"W\0\0\0");
+        entry(&mut tiff, 4, 5, 3, 116u32.to_le_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        for value in [48, 51, 30, 2, 17, 40] {
+            tiff.extend_from_slice(&(value * 10u32).to_le_bytes());
+            tiff.extend_from_slice(&10u32.to_le_bytes());
+        }
+        tiff
+    }
+    fn icc_profile_with_description() -> Vec<u8> {
+        // 128 bytes long header, then a tag table with a single 'desc' tag.
+        let mut icc = vec![0; 128];
+        icc.extend_from_slice(&1u32.to_be_bytes());
+        icc.extend_from_slice(b"desc");
+        icc.extend_from_slice(&144u32.to_be_bytes());
+        icc.extend_from_slice(&21u32.to_be_bytes());
+        icc.extend_from_slice(b"desc\0\0\0\0");
+        icc.extend_from_slice(&9u32.to_be_bytes());
+        icc.extend_from_slice(b"Test RGB\0");
+        icc
+    }
+    fn icc_profile() -> Vec<u8> {
+        (0..=255).collect()
+    }
 }
 #[test]
 fn test_basic_failing_single() {
@@ -49,6 +222,1199 @@ fn test_basic_success_parallel() {
     }
 }
 #[test]
+fn test_subsampling_single() {
+    let source = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_subsampling(Subsampling::S444)
+        .build()
+        .compress()
+        .unwrap();
+    let header = turbojpeg::read_header(source.as_slice()).unwrap();
+    assert_eq!(header.subsamp, turbojpeg::Subsamp::None);
+    let kept = Single::from_bytes(source.clone())
+        .with_subsampling(Subsampling::Auto)
+        .build()
+        .compress()
+        .unwrap();
+    let header = turbojpeg::read_header(kept.as_slice()).unwrap();
+    assert_eq!(header.subsamp, turbojpeg::Subsamp::None);
+    let halved = Single::from_bytes(source)
+        .with_subsampling(Subsampling::S422)
+        .build()
+        .compress()
+        .unwrap();
+    let header = turbojpeg::read_header(halved.as_slice()).unwrap();
+    assert_eq!(header.subsamp, turbojpeg::Subsamp::Sub2x1);
+}
+#[test]
+fn test_subsampling_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    for res in Parallel::from_vec(success)
+        .with_subsampling(Subsampling::Auto)
+        .build()
+        .into_iter()
+    {
+        assert!(res.is_ok());
+    }
+}
+#[test]
+fn test_target_size_single() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let target = bytes.len() / 2;
+    let output = Single::from_bytes(bytes)
+        .with_target_size(target)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert!(output.bytes().len() <= target);
+    assert!((1..=95).contains(&output.quality().unwrap()));
+}
+#[test]
+fn test_target_size_unreachable() {
+    let success = Dummy::create_jpeg_image();
+    let single = Single::from_bytes(success)
+        .with_target_size(10)
+        .build()
+        .compress();
+    assert!(matches!(single, Err(Error::TargetError(_))));
+}
+#[test]
+fn test_target_size_parallel() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let vec = std::fs::read_dir(test_dir_path)
+        .unwrap()
+        .flatten()
+        .filter(|direntry| direntry.path().is_file())
+        .map(|direntry| std::fs::read(direntry.path()).unwrap())
+        .collect::<Vec<Vec<u8>>>();
+    let smallest = vec.iter().map(|bytes| bytes.len()).min().unwrap();
+    let target = smallest / 2;
+    for res in Parallel::from_vec(vec)
+        .with_target_size(target)
+        .with_device(4)
+        .build()
+        .into_output_iter()
+    {
+        assert!(res.unwrap().bytes().len() <= target);
+    }
+}
+#[test]
+fn test_min_similarity_single() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let output = Single::from_bytes(bytes.clone())
+        .with_min_similarity(0.95)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert!(output.similarity().unwrap() >= 0.95);
+    let stricter = Single::from_bytes(bytes)
+        .with_min_similarity(0.99)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert!(stricter.quality() >= output.quality());
+}
+#[test]
+fn test_min_similarity_unreachable() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let single = Single::from_bytes(bytes)
+        .with_quality(10)
+        .with_min_similarity(1.0)
+        .build()
+        .compress();
+    assert!(matches!(single, Err(Error::TargetError(_))));
+}
+#[test]
+fn test_lossless_single() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let original = image::load_from_memory_with_format(bytes.as_slice(), image::ImageFormat::Jpeg)
+        .unwrap()
+        .into_rgb8();
+    for lossless in [Lossless::OptimizeHuffman, Lossless::Progressive] {
+        let output = Single::from_bytes(bytes.clone())
+            .with_lossless(lossless)
+            .build()
+            .compress_output()
+            .unwrap();
+        assert!(output.quality().is_none());
+        let recompressed =
+            image::load_from_memory_with_format(output.bytes(), image::ImageFormat::Jpeg)
+                .unwrap()
+                .into_rgb8();
+        assert_eq!(original, recompressed);
+    }
+}
+#[test]
+fn test_lossless_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    for (res, original) in Parallel::from_vec(success.clone())
+        .with_lossless(Lossless::OptimizeHuffman)
+        .build()
+        .into_iter()
+        .zip(success)
+    {
+        assert!(res.unwrap().len() <= original.len());
+    }
+}
+#[test]
+fn test_progressive_single() {
+    let with_metadata = Dummy::create_jpeg_image_with_metadata();
+    let compressed = Single::from_bytes(with_metadata)
+        .with_progressive(true)
+        .build()
+        .compress()
+        .unwrap();
+    let parts = JpegParts::from_bytes(compressed.into()).unwrap();
+    // SOF2 marks a progressive JPEG.
+    assert!(parts.segment_by_marker(0xC2).is_some());
+    assert!(parts.segment_by_marker(0xC0).is_none());
+    assert_eq!(parts.exif().unwrap().to_vec(), Dummy::exif());
+    assert_eq!(parts.icc_profile().unwrap().to_vec(), Dummy::icc_profile());
+}
+#[test]
+fn test_progressive_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image_with_metadata());
+    }
+    for res in Parallel::from_vec(success)
+        .with_progressive(true)
+        .build()
+        .into_iter()
+    {
+        let parts = JpegParts::from_bytes(res.unwrap().into()).unwrap();
+        assert!(parts.segment_by_marker(0xC2).is_some());
+        assert_eq!(parts.exif().unwrap().to_vec(), Dummy::exif());
+        assert_eq!(parts.icc_profile().unwrap().to_vec(), Dummy::icc_profile());
+    }
+}
+#[test]
+fn test_resize_single() {
+    let dimensions = |bytes: Vec<u8>| {
+        image::load_from_memory_with_format(bytes.as_slice(), image::ImageFormat::Jpeg)
+            .unwrap()
+            .into_rgb8()
+            .dimensions()
+    };
+    // 1000x1000 down to exactly 1/8 of its size, which only needs DCT scaling.
+    let exact = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_max_dimensions(125, 2000)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(dimensions(exact), (125, 125));
+    let filtered = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_max_dimensions(300, 200)
+        .with_filter(Filter::CatmullRom)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(dimensions(filtered), (200, 200));
+    let scaled = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_scale(0.33)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(dimensions(scaled), (330, 330));
+    let never_upscaled = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_max_dimensions(4000, 4000)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(dimensions(never_upscaled), (1000, 1000));
+}
+#[test]
+fn test_resize_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    for res in Parallel::from_vec(success)
+        .with_scale(0.5)
+        .with_filter(Filter::Triangle)
+        .build()
+        .into_iter()
+    {
+        let resized = image::load_from_memory_with_format(&res.unwrap(), image::ImageFormat::Jpeg)
+            .unwrap()
+            .into_rgb8();
+        assert_eq!(resized.dimensions(), (500, 500));
+    }
+}
+#[test]
+fn test_auto_orient_single() {
+    let oriented = Single::from_bytes(Dummy::create_rotated_jpeg_image())
+        .with_auto_orient(true)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(Dummy::orientation(&oriented), 1);
+    let img = image::load_from_memory_with_format(&oriented, image::ImageFormat::Jpeg)
+        .unwrap()
+        .into_rgb8();
+    assert_eq!(img.dimensions(), (20, 40));
+    assert!(img.get_pixel(10, 5).0[0] > 200);
+    assert!(img.get_pixel(10, 35).0[0] < 50);
+    let untouched = Single::from_bytes(Dummy::create_rotated_jpeg_image())
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(Dummy::orientation(&untouched), 6);
+    let img = image::load_from_memory_with_format(&untouched, image::ImageFormat::Jpeg)
+        .unwrap()
+        .into_rgb8();
+    assert_eq!(img.dimensions(), (40, 20));
+}
+#[test]
+fn test_auto_orient_parallel() {
+    let mut rotated = Vec::new();
+    for _ in 0..10 {
+        rotated.push(Dummy::create_rotated_jpeg_image());
+    }
+    for res in Parallel::from_vec(rotated)
+        .with_auto_orient(true)
+        .with_max_dimensions(10, 20)
+        .build()
+        .into_iter()
+    {
+        let oriented = res.unwrap();
+        assert_eq!(Dummy::orientation(&oriented), 1);
+        let img = image::load_from_memory_with_format(&oriented, image::ImageFormat::Jpeg)
+            .unwrap()
+            .into_rgb8();
+        assert_eq!(img.dimensions(), (10, 20));
+    }
+}
+#[test]
+fn test_metadata_single() {
+    let compress = |metadata: MetadataPolicy| {
+        Single::from_bytes(Dummy::create_jpeg_image_with_tags())
+            .with_metadata(metadata)
+            .build()
+            .compress()
+            .unwrap()
+    };
+    let kept = compress(MetadataPolicy::KeepAll);
+    let parts = JpegParts::from_bytes(kept.into()).unwrap();
+    assert_eq!(parts.exif().unwrap().to_vec(), Dummy::exif_with_tags());
+
+    let no_gps = compress(MetadataPolicy::StripGps);
+    assert!(!Dummy::exif_contains(&no_gps, b"WGS-84"));
+    assert!(Dummy::exif_contains(&no_gps, b"Canon"));
+    assert!(Dummy::exif_contains(&no_gps, b"2024:01:01 10:00:00"));
+    assert!(Dummy::exif_contains(&no_gps, b"SN123456"));
+    // values before the maker note move, but not the maker note, offsets within it may be relative to the EXIF data.
+    let no_copyright = compress(MetadataPolicy::Strip([tag::COPYRIGHT].into()));
+    let original = Dummy::create_jpeg_image_with_tags();
+    assert_eq!(
+        Dummy::exif_position(&no_copyright, b"MAKERNOTE!"),
+        Dummy::exif_position(&original, b"MAKERNOTE!")
+    );
+    assert!(
+        Dummy::exif_position(&no_copyright, b"2024:01:01")
+            < Dummy::exif_position(&original, b"2024:01:01")
+    );
+    assert!(Dummy::exif_contains(&no_copyright, b"SN123456"));
+
+    let stripped = compress(MetadataPolicy::Strip(
+        [tag::MAKER_NOTE, tag::BODY_SERIAL_NUMBER].into(),
+    ));
+    assert!(!Dummy::exif_contains(&stripped, b"MAKERNOTE!"));
+    assert!(!Dummy::exif_contains(&stripped, b"SN123456"));
+    assert!(Dummy::exif_contains(&stripped, b"2024:01:01 10:00:00"));
+    assert!(Dummy::exif_contains(&stripped, b"WGS-84"));
+
+    let policy = MetadataPolicy::Keep([tag::DATE_TIME_ORIGINAL, tag::COPYRIGHT].into());
+    let selected = compress(policy.clone());
+    assert!(Dummy::exif_contains(&selected, b"2024:01:01 10:00:00"));
+    assert!(Dummy::exif_contains(&selected, b"(c) someone"));
+    assert!(!Dummy::exif_contains(&selected, b"Canon"));
+    assert!(!Dummy::exif_contains(&selected, b"SN123456"));
+    assert!(!Dummy::exif_contains(&selected, b"WGS-84"));
+    // rewritten EXIF data is parsed again and kept as is.
+    let again = Single::from_bytes(selected.clone())
+        .with_metadata(policy)
+        .build()
+        .compress()
+        .unwrap();
+    let exif = |jpeg: Vec<u8>| JpegParts::from_bytes(jpeg.into()).unwrap().exif();
+    assert_eq!(exif(again), exif(selected));
+
+    let none = JpegParts::from_bytes(compress(MetadataPolicy::StripAll).into()).unwrap();
+    assert!(none.exif().is_none());
+    assert_eq!(none.icc_profile().unwrap().to_vec(), Dummy::icc_profile());
+}
+#[test]
+fn test_metadata_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image_with_tags());
+    }
+    for res in Parallel::from_vec(success)
+        .with_metadata(MetadataPolicy::StripGps)
+        .build()
+        .into_iter()
+    {
+        let compressed = res.unwrap();
+        assert!(!Dummy::exif_contains(&compressed, b"WGS-84"));
+        assert!(Dummy::exif_contains(&compressed, b"SN123456"));
+    }
+}
+#[test]
+fn test_ancillary_single() {
+    let compressed = Single::from_bytes(Dummy::create_jpeg_image_with_ancillary())
+        .build()
+        .compress()
+        .unwrap();
+    for (marker, contents) in Dummy::ancillary() {
+        let preserved = Dummy::segments(&compressed, marker);
+        if marker == 0xEE {
+            // compressed images are YCbCr.
+            assert_eq!(preserved[0][11], 1);
+        } else {
+            assert_eq!(preserved, vec![contents]);
+        }
+    }
+    let only_xmp = Single::from_bytes(Dummy::create_jpeg_image_with_ancillary())
+        .with_ancillary([Ancillary::Xmp, Ancillary::App(3)])
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(Dummy::segments(&only_xmp, 0xE1).len(), 1);
+    assert_eq!(Dummy::segments(&only_xmp, 0xE3).len(), 1);
+    assert!(Dummy::segments(&only_xmp, 0xED).is_empty());
+    assert!(Dummy::segments(&only_xmp, 0xEE).is_empty());
+    assert!(Dummy::segments(&only_xmp, 0xFE).is_empty());
+    let none = Single::from_bytes(Dummy::create_jpeg_image_with_ancillary())
+        .with_ancillary(Vec::new())
+        .build()
+        .compress()
+        .unwrap();
+    for (marker, _) in Dummy::ancillary() {
+        assert!(Dummy::segments(&none, marker).is_empty());
+    }
+}
+#[test]
+fn test_ancillary_metadata_policy() {
+    let mut jpeg = JpegParts::from_bytes(Dummy::create_jpeg_image_with_tags().into()).unwrap();
+    let xmp = b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta><rdf:Description exif:GPSLatitude=\"48,51.4N\" exif:GPSLongitude=\"2,21.0E\"/></x:xmpmeta>";
+    let segment = JpegSegment::new_with_contents(0xE1, xmp.to_vec().into());
+    jpeg.segments_mut().insert(1, segment);
+    let original = jpeg.encoder().bytes().to_vec();
+    let compress = |metadata: MetadataPolicy| {
+        Single::from_bytes(original.clone())
+            .with_metadata(metadata)
+            .build()
+            .compress()
+            .unwrap()
+    };
+    let contains =
+        |jpeg: &[u8], needle: &[u8]| jpeg.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(&compress(MetadataPolicy::KeepAll), b"GPSLatitude"));
+    // XMP may hold the same data as EXIF data, so stripping EXIF data drops it as well.
+    let no_gps = compress(MetadataPolicy::StripGps);
+    assert!(!contains(&no_gps, b"GPSLatitude"));
+    assert!(!contains(&no_gps, b"WGS-84"));
+    assert!(Dummy::segments(&no_gps, 0xE1).is_empty());
+    assert!(Dummy::exif_contains(&no_gps, b"Canon"));
+}
+#[test]
+fn test_ancillary_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image_with_ancillary());
+    }
+    for res in Parallel::from_vec(success)
+        .with_lossless(Lossless::OptimizeHuffman)
+        .build()
+        .into_iter()
+    {
+        let compressed = res.unwrap();
+        // lossless recompressions keep the Adobe APP14 segment as is.
+        for (marker, contents) in Dummy::ancillary() {
+            assert_eq!(Dummy::segments(&compressed, marker), vec![contents]);
+        }
+    }
+}
+#[test]
+fn test_metadata_inspect() {
+    let compressed = Single::from_bytes(Dummy::create_jpeg_image_with_tags())
+        .with_quality(80)
+        .with_subsampling(Subsampling::S444)
+        .build()
+        .compress()
+        .unwrap();
+    let metadata = Metadata::from_bytes(compressed.as_slice()).unwrap();
+    assert_eq!(metadata.dimensions(), (1000, 1000));
+    assert_eq!(metadata.components(), 3);
+    assert_eq!(metadata.subsampling(), Some(Subsampling::S444));
+    assert_eq!(metadata.quality(), Some(80));
+    assert!(!metadata.is_progressive());
+    assert_eq!(metadata.date_time(), Some("2024:01:01 10:00:00"));
+    assert_eq!(metadata.make(), Some("Canon"));
+    assert_eq!(metadata.model(), None);
+    assert_eq!(metadata.gps(), None);
+    let identifiers: Vec<&str> = metadata
+        .app_segments()
+        .iter()
+        .map(|segment| segment.identifier())
+        .collect();
+    assert!(identifiers.contains(&"JFIF"));
+    assert!(identifiers.contains(&"Exif"));
+    assert!(identifiers.contains(&"ICC_PROFILE"));
+
+    let progressive = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_quality(30)
+        .with_progressive(true)
+        .build()
+        .compress()
+        .unwrap();
+    let metadata = Metadata::from_bytes(progressive.as_slice()).unwrap();
+    assert!(metadata.is_progressive());
+    assert_eq!(metadata.quality(), Some(30));
+    assert_eq!(metadata.subsampling(), Some(Subsampling::S420));
+
+    let mut jpeg = JpegParts::from_bytes(Dummy::create_jpeg_image().into()).unwrap();
+    jpeg.set_exif(Some(Dummy::exif_with_gps().into()));
+    jpeg.set_icc_profile(Some(Dummy::icc_profile_with_description().into()));
+    let metadata = Metadata::from_bytes(&jpeg.encoder().bytes()).unwrap();
+    assert_eq!(metadata.orientation(), Some(3));
+    let (latitude, longitude) = metadata.gps().unwrap();
+    assert!((latitude - 48.858333).abs() < 1e-6);
+    assert!((longitude + 2.294444).abs() < 1e-6);
+    assert_eq!(metadata.icc_description(), Some("Test RGB"));
+
+    assert!(Metadata::from_bytes(Dummy::create_failing_image().as_slice()).is_err());
+}
+#[test]
+fn test_skip_if_lower_quality_single() {
+    let source = Single::from_bytes(Dummy::create_jpeg_image_with_metadata())
+        .with_quality(70)
+        .build()
+        .compress()
+        .unwrap();
+    let kept = Single::from_bytes(source.clone())
+        .with_quality(80)
+        .with_skip_if_lower_quality(true)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(kept.outcome(), Outcome::SourceQualityLower);
+    assert_eq!(kept.quality(), None);
+    assert_eq!(kept.bytes(), source.as_slice());
+    let compressed = Single::from_bytes(source.clone())
+        .with_quality(20)
+        .with_skip_if_lower_quality(true)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(compressed.outcome(), Outcome::Compressed);
+    assert_eq!(compressed.quality(), Some(20));
+    assert!(compressed.bytes().len() < source.len());
+    // searches are capped at the quality of the source.
+    // dropping a large comment of the source makes sure the compressed image is smaller.
+    let mut jpeg = JpegParts::from_bytes(source.clone().into()).unwrap();
+    let comment = JpegSegment::new_with_contents(0xFE, vec![b'x'; 10_000].into());
+    jpeg.segments_mut().insert(1, comment);
+    let commented = jpeg.encoder().bytes().to_vec();
+    let search = |skip: bool| {
+        Single::from_bytes(commented.clone())
+            .with_target_size(commented.len() * 2)
+            .with_ancillary(Vec::new())
+            .with_skip_if_lower_quality(skip)
+            .build()
+            .compress_output()
+            .unwrap()
+    };
+    assert!(search(false).quality() > Some(70));
+    let searched = search(true);
+    assert_eq!(searched.outcome(), Outcome::Compressed);
+    assert!(searched.quality() <= Some(70));
+    // optimizing Huffman tables twice does not make the image any smaller.
+    let optimized = Single::from_bytes(source)
+        .with_lossless(Lossless::OptimizeHuffman)
+        .build()
+        .compress()
+        .unwrap();
+    let not_smaller = Single::from_bytes(optimized.clone())
+        .with_lossless(Lossless::OptimizeHuffman)
+        .with_skip_if_lower_quality(true)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(not_smaller.outcome(), Outcome::NotSmaller);
+    assert_eq!(not_smaller.into_bytes(), optimized);
+}
+#[test]
+fn test_skip_if_lower_quality_parallel() {
+    let source = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_quality(70)
+        .build()
+        .compress()
+        .unwrap();
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(source.clone());
+    }
+    for res in Parallel::from_vec(success)
+        .with_quality(90)
+        .with_skip_if_lower_quality(true)
+        .build()
+        .into_output_iter()
+    {
+        let output = res.unwrap();
+        assert_eq!(output.outcome(), Outcome::SourceQualityLower);
+        assert_eq!(output.into_bytes(), source);
+    }
+}
+#[test]
+fn test_size_guard_single() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let source = Single::from_bytes(bytes)
+        .with_quality(70)
+        .build()
+        .compress()
+        .unwrap();
+    // re-encoding with a higher quality grows the image.
+    let kept = Single::from_bytes(source.clone())
+        .with_quality(100)
+        .with_size_guard(0.0)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(kept.outcome(), Outcome::NotSmaller);
+    assert_eq!(kept.quality(), None);
+    assert_eq!(kept.bytes(), source.as_slice());
+    let compressed = Single::from_bytes(source.clone())
+        .with_quality(20)
+        .with_size_guard(0.05)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(compressed.outcome(), Outcome::Compressed);
+    assert!(compressed.bytes().len() as f64 <= source.len() as f64 * 0.95);
+    // no image can save its whole size.
+    let whole = Single::from_bytes(source.clone())
+        .with_quality(20)
+        .with_size_guard(1.0)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(whole.outcome(), Outcome::NotSmaller);
+    assert_eq!(whole.into_bytes(), source);
+}
+#[test]
+fn test_size_guard_parallel() {
+    let source = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_quality(70)
+        .build()
+        .compress()
+        .unwrap();
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(source.clone());
+    }
+    for res in Parallel::from_vec(success)
+        .with_quality(100)
+        .with_size_guard(0.0)
+        .build()
+        .into_output_iter()
+    {
+        let output = res.unwrap();
+        assert_eq!(output.outcome(), Outcome::NotSmaller);
+        assert_eq!(output.into_bytes(), source);
+    }
+}
+#[test]
+fn test_output_details_single() {
+    let original = Dummy::create_jpeg_image_with_metadata();
+    let output = Single::from_bytes(original.clone())
+        .with_metadata(MetadataPolicy::StripAll)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(output.index(), 0);
+    assert_eq!(output.original_size(), original.len());
+    // details are about the original image, whatever is preserved.
+    assert!(output.has_exif());
+    assert!(output.has_icc());
+    assert!(output.duration() > std::time::Duration::ZERO);
+    let output = Single::from_bytes(Dummy::create_jpeg_image())
+        .build()
+        .compress_output()
+        .unwrap();
+    assert!(!output.has_exif());
+    assert!(!output.has_icc());
+}
+#[test]
+fn test_output_details_parallel() {
+    let mut success = Vec::new();
+    for i in 0..10 {
+        success.push(match i % 2 {
+            0 => Dummy::create_jpeg_image(),
+            _ => Dummy::create_jpeg_image_with_metadata(),
+        });
+    }
+    let sizes: Vec<usize> = success.iter().map(Vec::len).collect();
+    for (i, res) in Parallel::from_vec(success)
+        .build()
+        .into_output_iter()
+        .enumerate()
+    {
+        let output = res.unwrap();
+        assert_eq!(output.index(), i);
+        assert_eq!(output.original_size(), sizes[i]);
+        assert_eq!(output.has_exif(), i % 2 == 1);
+        assert_eq!(output.has_icc(), i % 2 == 1);
+    }
+}
+#[test]
+fn test_warnings_single() {
+    let output = Single::from_bytes(Dummy::create_jpeg_image())
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(output.warnings(), &[Warning::NoExif, Warning::NoIccProfile]);
+    let output = Single::from_bytes(Dummy::create_jpeg_image_with_metadata())
+        .build()
+        .compress_output()
+        .unwrap();
+    assert!(output.warnings().is_empty());
+    let mut malformed =
+        JpegParts::from_bytes(Dummy::create_jpeg_image_with_metadata().into()).unwrap();
+    malformed.set_exif(Some(b"not a TIFF structure".to_vec().into()));
+    let output = Single::from_bytes(malformed.encoder().bytes().to_vec())
+        .with_metadata(MetadataPolicy::StripGps)
+        .build()
+        .compress_output()
+        .unwrap();
+    assert_eq!(output.warnings(), &[Warning::MalformedExif]);
+    assert_eq!(
+        JpegParts::from_bytes(output.into_bytes().into())
+            .unwrap()
+            .exif(),
+        None
+    );
+}
+#[test]
+fn test_warnings_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image_with_metadata());
+    }
+    for res in Parallel::from_vec(success)
+        .with_lossless(Lossless::OptimizeHuffman)
+        .build()
+        .into_output_iter()
+    {
+        assert!(res.unwrap().warnings().is_empty());
+    }
+}
+#[test]
+fn test_from_iter_parallel() {
+    let images = (0..10).map(|i| match i % 3 {
+        0 => Dummy::create_failing_image(),
+        _ => Dummy::create_jpeg_image(),
+    });
+    let mut count = 0;
+    for (i, res) in Parallel::from_iter(images)
+        .build()
+        .into_output_iter()
+        .enumerate()
+    {
+        match i % 3 {
+            0 => assert!(res.is_err()),
+            _ => assert_eq!(res.unwrap().index(), i),
+        }
+        count += 1;
+    }
+    assert_eq!(count, 10);
+}
+#[test]
+fn test_from_channel_parallel() {
+    let (sender, builder) = Parallel::from_channel(2);
+    let producer = thread::spawn(move || {
+        for _ in 0..10 {
+            sender.send(Dummy::create_jpeg_image()).unwrap();
+        }
+    });
+    let mut count = 0;
+    for (i, res) in builder
+        .with_device(4)
+        .build()
+        .into_output_iter()
+        .enumerate()
+    {
+        assert_eq!(res.unwrap().index(), i);
+        count += 1;
+    }
+    producer.join().unwrap();
+    assert_eq!(count, 10);
+}
+#[test]
+fn test_buffer_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    for buffer in [0, 1] {
+        let mut iter = Parallel::from_vec(success.clone())
+            .with_buffer(buffer)
+            .with_device(4)
+            .build()
+            .into_output_iter();
+        // a slow consumer does not lose or reorder images.
+        thread::sleep(std::time::Duration::from_millis(500));
+        for i in 0..10 {
+            assert_eq!(iter.next().unwrap().unwrap().index(), i);
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(iter.next().is_none());
+        // while the consumer is stalled, at most one image per thread and one per buffer slot is pulled.
+        let pulled = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = std::sync::Arc::clone(&pulled);
+        let images = (0..20).map(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Dummy::create_jpeg_image()
+        });
+        let mut iter = Parallel::from_iter(images)
+            .with_buffer(buffer)
+            .with_device(4)
+            .build()
+            .into_iter();
+        thread::sleep(std::time::Duration::from_millis(500));
+        assert!(pulled.load(std::sync::atomic::Ordering::SeqCst) <= 4 + buffer);
+        assert_eq!(iter.by_ref().count(), 20);
+        assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), 20);
+    }
+}
+#[test]
+fn test_as_completed_parallel() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let mut images = vec![std::fs::read(test_dir_path.join("1.JPG")).unwrap()];
+    for i in 1..10 {
+        images.push(match i % 3 {
+            0 => Dummy::create_failing_image(),
+            _ => {
+                let mut jpeg = Vec::new();
+                let img = RgbImage::new(16, 16);
+                img.write_to(&mut Cursor::new(&mut jpeg), Jpeg).unwrap();
+                jpeg
+            }
+        });
+    }
+    let mut indexes = Vec::new();
+    for (index, res) in Parallel::from_vec(images)
+        .with_ordering(Ordering::AsCompleted)
+        .build()
+        .into_indexed_iter()
+    {
+        match index % 3 {
+            0 if index > 0 => assert!(res.is_err()),
+            _ => assert_eq!(res.unwrap().index(), index),
+        }
+        indexes.push(index);
+    }
+    // small images do not wait for the large first one.
+    assert_ne!(indexes[0], 0);
+    indexes.sort();
+    assert_eq!(indexes, (0..10).collect::<Vec<usize>>());
+}
+#[test]
+fn test_indexed_in_order_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..10 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    for (i, (index, res)) in Parallel::from_vec(success)
+        .build()
+        .into_indexed_iter()
+        .enumerate()
+    {
+        assert_eq!(index, i);
+        assert!(res.is_ok());
+    }
+}
+#[test]
+fn test_mixed_sizes_in_order_parallel() {
+    let test_dir_path = PathBuf::from(TEST_DIR);
+    let large = std::fs::read(test_dir_path.join("1.JPG")).unwrap();
+    let mut small = Vec::new();
+    let img = RgbImage::new(16, 16);
+    img.write_to(&mut Cursor::new(&mut small), Jpeg).unwrap();
+    let mut images = Vec::new();
+    for i in 0..12 {
+        images.push(match i % 4 {
+            0 => large.clone(),
+            3 => Dummy::create_failing_image(),
+            _ => small.clone(),
+        });
+    }
+    // small images finish ahead of the large ones, even with no room left in the buffer.
+    let mut count = 0;
+    for (i, (index, res)) in Parallel::from_vec(images)
+        .with_buffer(0)
+        .with_device(4)
+        .build()
+        .into_indexed_iter()
+        .enumerate()
+    {
+        assert_eq!(index, i);
+        match i % 4 {
+            3 => assert!(res.is_err()),
+            _ => assert_eq!(res.unwrap().index(), i),
+        }
+        count += 1;
+    }
+    assert_eq!(count, 12);
+}
+#[cfg(feature = "rayon")]
+#[test]
+fn test_pool_parallel() {
+    let pool = std::sync::Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap(),
+    );
+    let mut images = Vec::new();
+    for i in 0..10 {
+        images.push(match i % 4 {
+            3 => Dummy::create_failing_image(),
+            _ => Dummy::create_jpeg_image(),
+        });
+    }
+    // the same pool can be used by several compression tasks.
+    for _ in 0..2 {
+        let mut count = 0;
+        for (i, (index, res)) in Parallel::from_vec(images.clone())
+            .with_pool(std::sync::Arc::clone(&pool))
+            .build()
+            .into_indexed_iter()
+            .enumerate()
+        {
+            assert_eq!(index, i);
+            assert_eq!(res.is_err(), i % 4 == 3);
+            count += 1;
+        }
+        assert_eq!(count, 10);
+    }
+}
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_compress_async_single() {
+    let compressed = Single::from_bytes(Dummy::create_jpeg_image())
+        .build()
+        .compress_async()
+        .await;
+    assert!(compressed.is_ok());
+    let failed = Single::from_bytes(Dummy::create_failing_image())
+        .build()
+        .compress_async()
+        .await;
+    assert!(failed.is_err());
+}
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_stream_parallel() {
+    use futures::StreamExt;
+    let mut images = Vec::new();
+    for i in 0..10 {
+        images.push(match i % 4 {
+            3 => Dummy::create_failing_image(),
+            _ => Dummy::create_jpeg_image(),
+        });
+    }
+    let results = Parallel::from_vec(images.clone())
+        .build()
+        .into_stream()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(results.len(), 10);
+    for (i, res) in results.into_iter().enumerate() {
+        assert_eq!(res.is_err(), i % 4 == 3);
+    }
+    // dropping the stream early does not hang.
+    let mut stream = Parallel::from_vec(images)
+        .with_buffer(0)
+        .build()
+        .into_stream();
+    assert!(stream.next().await.unwrap().is_ok());
+    drop(stream);
+    // nor does it keep pulling images: besides one image per thread, only the image returned,
+    // the one waiting in the stream and the one being handed over to it are pulled.
+    let pulled = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = std::sync::Arc::clone(&pulled);
+    let images = (0..50).map(move |_| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Dummy::create_jpeg_image()
+    });
+    let mut stream = Parallel::from_iter(images)
+        .with_buffer(0)
+        .with_device(2)
+        .build()
+        .into_stream();
+    assert!(stream.next().await.unwrap().is_ok());
+    drop(stream);
+    thread::sleep(std::time::Duration::from_millis(500));
+    assert!(pulled.load(std::sync::atomic::Ordering::SeqCst) <= 2 + 3);
+}
+#[test]
+fn test_abort_parallel() {
+    let mut success = Vec::new();
+    for _ in 0..50 {
+        success.push(Dummy::create_jpeg_image());
+    }
+    let mut iter = Parallel::from_vec(success.clone())
+        .with_buffer(0)
+        .build()
+        .into_iter();
+    assert!(iter.next().unwrap().is_ok());
+    iter.abort();
+    assert!(iter.abort_handle().is_aborted());
+    assert!(iter.next().is_none());
+    // aborting from another thread ends the iteration early.
+    let iter = Parallel::from_vec(success).build().into_indexed_iter();
+    let abort = iter.abort_handle();
+    let mut count = 0;
+    for (_index, res) in iter {
+        assert!(res.is_ok());
+        count += 1;
+        if count == 2 {
+            let abort = abort.clone();
+            thread::spawn(move || abort.abort()).join().unwrap();
+        }
+    }
+    assert_eq!(count, 2);
+}
+#[test]
+fn test_panicking_input_parallel() {
+    // a panic while pulling images is reported in place of the image, and ends the iteration.
+    let images = (0..10).map(|i| {
+        assert!(i < 5, "no more images");
+        Dummy::create_jpeg_image()
+    });
+    let results = Parallel::from_iter(images)
+        .with_device(4)
+        .build()
+        .into_indexed_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 6);
+    assert!(results[..5].iter().all(|(_, res)| res.is_ok()));
+    assert!(matches!(
+        results[5],
+        (5, Err(Error::InputPanicked { index: 5 }))
+    ));
+}
+#[cfg(feature = "tracing")]
+#[test]
+fn test_panicking_worker_parallel() {
+    use tracing::{field::Field, span, Event, Metadata, Subscriber};
+    /// Panics when the compression of an image of `size` bytes starts, from within the compression.
+    struct PanicOnSize(usize);
+    struct Size(Option<u64>);
+    impl tracing::field::Visit for Size {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "size" {
+                self.0 = Some(value);
+            }
+        }
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+    impl Subscriber for PanicOnSize {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut size = Size(None);
+            span.record(&mut size);
+            if span.metadata().name() == "compress" && size.0 == Some(self.0 as u64) {
+                panic!("compression panicked");
+            }
+            span::Id::from_u64(1)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+    // a comment makes the panicking image a size no other test compresses.
+    let mut jpeg = JpegParts::from_bytes(Dummy::create_jpeg_image().into()).unwrap();
+    let segment = JpegSegment::new_with_contents(0xFE, vec![b'x'; 4321].into());
+    jpeg.segments_mut().insert(1, segment);
+    let panicking = jpeg.encoder().bytes().to_vec();
+    tracing::subscriber::set_global_default(PanicOnSize(panicking.len())).unwrap();
+    let mut images = vec![Dummy::create_jpeg_image(); 6];
+    images[3] = panicking;
+    // a panic while compressing an image is reported in place of the image, and the other images are still compressed.
+    let results = Parallel::from_vec(images)
+        .with_device(4)
+        .build()
+        .into_indexed_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 6);
+    for (i, (index, res)) in results.iter().enumerate() {
+        assert_eq!(*index, i);
+        match i {
+            3 => assert!(matches!(res, Err(Error::WorkerPanicked { index: 3 }))),
+            _ => assert!(res.is_ok()),
+        }
+    }
+}
+#[test]
+fn test_progress_parallel() {
+    let mut images = Vec::new();
+    for i in 0..10 {
+        images.push(match i % 4 {
+            3 => Dummy::create_failing_image(),
+            _ => Dummy::create_jpeg_image(),
+        });
+    }
+    let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let local_reported = std::sync::Arc::clone(&reported);
+    let results = Parallel::from_vec(images.clone())
+        .with_device(4)
+        .with_progress(move |progress| local_reported.lock().unwrap().push(progress))
+        .build()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let reported = reported.lock().unwrap();
+    assert_eq!(reported.len(), 10);
+    for (i, progress) in reported.iter().enumerate() {
+        assert_eq!(progress.completed(), i + 1);
+        assert_eq!(progress.total(), Some(10));
+    }
+    let last = reported.last().unwrap();
+    let bytes_in = images.iter().map(|image| image.len() as u64).sum::<u64>();
+    let bytes_out = results
+        .iter()
+        .flatten()
+        .map(|bytes| bytes.len() as u64)
+        .sum::<u64>();
+    assert_eq!(last.bytes_in(), bytes_in);
+    assert_eq!(last.bytes_out(), bytes_out);
+}
+#[test]
+fn test_panicking_progress_parallel() {
+    // a panicking callback neither fails the images nor stops later reports.
+    let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let local_reported = std::sync::Arc::clone(&reported);
+    let results = Parallel::from_vec(vec![Dummy::create_jpeg_image(); 6])
+        .with_device(2)
+        .with_progress(move |progress| {
+            local_reported.lock().unwrap().push(progress.completed());
+            assert!(progress.completed() % 2 == 0, "odd progress");
+        })
+        .build()
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 6);
+    assert!(results.iter().all(|res| res.is_ok()));
+    assert_eq!(*reported.lock().unwrap(), (1..=6).collect::<Vec<usize>>());
+}
+#[test]
+fn test_progress_single() {
+    let passes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let local_passes = std::sync::Arc::clone(&passes);
+    let output = Single::from_bytes(Dummy::create_jpeg_image())
+        .with_target_size(20 * 1024)
+        .with_progress(move |progress| local_passes.lock().unwrap().push(progress.completed()))
+        .build()
+        .compress();
+    assert!(output.is_ok());
+    let passes = passes.lock().unwrap();
+    // a binary search over qualities from 1 to 95 takes at most 7 passes.
+    assert!(!passes.is_empty() && passes.len() <= 7);
+    assert_eq!(*passes, (1..=passes.len()).collect::<Vec<usize>>());
+}
+#[test]
+fn test_directory() {
+    let input = tempdir::TempDir::new("input").unwrap();
+    let nested = input.path().join("nested");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(input.path().join("a.jpg"), Dummy::create_jpeg_image()).unwrap();
+    std::fs::write(input.path().join("b.JPEG"), Dummy::create_jpeg_image()).unwrap();
+    std::fs::write(
+        input.path().join("broken.jpg"),
+        Dummy::create_failing_image(),
+    )
+    .unwrap();
+    std::fs::write(input.path().join("notes.txt"), b"not an image").unwrap();
+    std::fs::write(nested.join("c.JPG"), Dummy::create_jpeg_image()).unwrap();
+    // the output directory may be inside the input directory when it is not compressed recursively.
+    let output = input.path().join("compressed");
+    let reports = Directory::from_paths(input.path(), &output)
+        .build()
+        .compress()
+        .unwrap();
+    let names = reports
+        .iter()
+        .map(|report| report.input().file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<&str>>();
+    assert_eq!(names, vec!["a.jpg", "b.JPEG", "broken.jpg"]);
+    assert!(reports[2].error().is_some());
+    assert!(!output.join("broken.jpg").exists());
+    for report in &reports[..2] {
+        assert!(report.error().is_none());
+        let written = std::fs::read(report.output()).unwrap();
+        assert_eq!(written.len(), report.compressed_size());
+    }
+    // originals are never overwritten, nor compressed images compressed again.
+    assert!(Directory::from_paths(input.path(), input.path())
+        .build()
+        .compress()
+        .is_err());
+    assert!(Directory::from_paths(input.path(), &output)
+        .with_recursive(true)
+        .build()
+        .compress()
+        .is_err());
+    std::fs::remove_dir_all(&output).unwrap();
+    let output = tempdir::TempDir::new("output").unwrap();
+    let reports = Directory::from_paths(input.path(), output.path().join("new"))
+        .with_recursive(true)
+        // images given to the builder are replaced by the files of the input directory.
+        .with_parallel(|_| {
+            Parallel::from_vec(Vec::new())
+                .with_quality(50)
+                .with_device(4)
+        })
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(reports.len(), 4);
+    assert!(output
+        .path()
+        .join("new")
+        .join("nested")
+        .join("c.JPG")
+        .is_file());
+    let extensions = vec![String::from("jpeg")];
+    let reports = Directory::from_paths(input.path(), output.path().join("jpeg"))
+        .with_extensions(extensions)
+        .build()
+        .compress()
+        .unwrap();
+    assert_eq!(reports.len(), 1);
+    assert!(
+        Directory::from_paths(input.path().join("missing"), output.path())
+            .build()
+            .compress()
+            .is_err()
+    );
+}
+#[test]
 fn test_basic_single_eq() {
     let test_dir_path = PathBuf::from(TEST_DIR);
     let bytes = std::fs::read(test_dir_path.join("1.JPG")).unwrap();